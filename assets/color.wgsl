@@ -1,30 +1,68 @@
-struct Matrices {
-    world: mat4x4<f32>,
+#include "lighting.wgsl"
+
+// Group 0 is shared by all the scene materials, see `SceneBindings`.
+struct Camera {
     view_proj: mat4x4<f32>,
-};
+    view: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+}
+
+// Must match `ObjectUniform`.
+struct Object {
+    world: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    receives_shadows: u32,
+}
 
 @group(0) @binding(0)
-var<uniform> matrices: Matrices;
+var<uniform> camera: Camera;
+
+// Indexed by the instance index.
+@group(0) @binding(1)
+var<storage, read> objects: array<Object>;
+
+struct Color {
+    color: vec4<f32>,
+    lit: u32,
+}
 
 @group(1) @binding(0)
-var<uniform> color: vec3<f32>;
+var<uniform> color: Color;
+
+// Fragments with lower alpha are discarded, set for `BlendMode::Cutout`.
+override alpha_cutoff: f32 = 0.0;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) @interpolate(flat) receives_shadows: u32,
 }
 
 @vertex
-fn vs_main(in: VertexInput) -> VertexOutput {
+fn vs_main(in: VertexInput, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let object = objects[instance];
     var out: VertexOutput;
-    out.clip_position = matrices.view_proj * matrices.world * vec4<f32>(in.position, 1.0);
+    let world_pos = object.world * vec4<f32>(in.position, 1.0);
+    out.world_pos = world_pos.xyz;
+    out.normal = (object.normal * vec4<f32>(in.normal, 0.0)).xyz;
+    out.clip_position = camera.view_proj * world_pos;
+    out.receives_shadows = object.receives_shadows;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(color, 1.0);
+    if color.color.a < alpha_cutoff {
+        discard;
+    }
+    if color.lit == 0u {
+        return color.color;
+    }
+    return vec4<f32>(shade(color.color.rgb, in.world_pos, in.normal, in.receives_shadows), color.color.a);
 }
@@ -0,0 +1,66 @@
+// Renders one cubemap face per pass from an equirectangular image.
+// The face index comes in as the instance index.
+
+struct VertexOutput {
+    @builtin(position)
+    clip_position: vec4<f32>,
+
+    @location(0)
+    ndc: vec2<f32>,
+
+    @location(1) @interpolate(flat)
+    face: u32,
+}
+
+// Full-screen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_idx: u32, @builtin(instance_index) face: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((vertex_idx << 1u) & 2u), f32(vertex_idx & 2u));
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    out.face = face;
+    return out;
+}
+
+// Float32 textures aren't filterable without an extra feature, so filtering is done manually.
+@group(0) @binding(0)
+var equirect: texture_2d<f32>;
+
+const PI: f32 = 3.14159265359;
+
+fn face_dir(face: u32, ndc: vec2<f32>) -> vec3<f32> {
+    // Texture rows go top to bottom in cubemap faces.
+    let s = ndc.x;
+    let t = -ndc.y;
+    switch face {
+        case 0u: { return vec3<f32>(1.0, -t, -s); }
+        case 1u: { return vec3<f32>(-1.0, -t, s); }
+        case 2u: { return vec3<f32>(s, 1.0, t); }
+        case 3u: { return vec3<f32>(s, -1.0, -t); }
+        case 4u: { return vec3<f32>(s, -t, 1.0); }
+        default: { return vec3<f32>(-s, -t, -1.0); }
+    }
+}
+
+fn load(coords: vec2<i32>, size: vec2<i32>) -> vec4<f32> {
+    // Wrapping horizontally, clamping vertically.
+    let x = (coords.x % size.x + size.x) % size.x;
+    let y = clamp(coords.y, 0, size.y - 1);
+    return textureLoad(equirect, vec2<i32>(x, y), 0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dir = normalize(face_dir(in.face, in.ndc));
+    let uv = vec2<f32>(atan2(dir.z, dir.x) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+
+    let size = vec2<i32>(textureDimensions(equirect));
+    let texel = uv * vec2<f32>(size) - 0.5;
+    let base = vec2<i32>(floor(texel));
+    let f = fract(texel);
+
+    let top = mix(load(base, size), load(base + vec2<i32>(1, 0), size), f.x);
+    let bottom = mix(load(base + vec2<i32>(0, 1), size), load(base + vec2<i32>(1, 1), size), f.x);
+    return vec4<f32>(mix(top, bottom, f.y).rgb, 1.0);
+}
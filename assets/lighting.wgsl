@@ -0,0 +1,98 @@
+// Lights and shadows shared by the lit scene shaders, prepended to the ones starting with
+// `#include "lighting.wgsl"`. Uses the scene bindings of group 0, see `SceneBindings`.
+
+// Must match `LightsUniform`.
+const MAX_LIGHTS: u32 = 8u;
+const LIGHT_DIRECTIONAL: u32 = 0u;
+const LIGHT_SPOT: u32 = 2u;
+const SHININESS: f32 = 32.0;
+const SPECULAR_STRENGTH: f32 = 0.3;
+// Pushes the shadow lookup off the surface, in addition to the depth bias of the shadow pass.
+const SHADOW_NORMAL_OFFSET: f32 = 0.05;
+
+struct Light {
+    position: vec4<f32>,
+    direction: vec4<f32>,
+    color: vec4<f32>,
+    kind: u32,
+    range: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+}
+
+struct Lights {
+    camera_pos: vec4<f32>,
+    ambient: vec4<f32>,
+    shadow_view_proj: mat4x4<f32>,
+    count: u32,
+    shadow_light: u32,
+    items: array<Light, MAX_LIGHTS>,
+}
+
+@group(0) @binding(2)
+var<uniform> lights: Lights;
+
+@group(0) @binding(3)
+var shadow_map: texture_depth_2d;
+
+@group(0) @binding(4)
+var shadow_sampler: sampler_comparison;
+
+// 3x3 PCF on top of the 2x2 one the linear comparison sampler does.
+fn shadow(world_pos: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let pos = lights.shadow_view_proj * vec4<f32>(world_pos + normal * SHADOW_NORMAL_OFFSET, 1.0);
+    let ndc = pos.xyz / pos.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Blinn-Phong
+fn shade(albedo: vec3<f32>, world_pos: vec3<f32>, normal: vec3<f32>, receives_shadows: u32) -> vec3<f32> {
+    let n = normalize(normal);
+    let view_dir = normalize(lights.camera_pos.xyz - world_pos);
+    var result = lights.ambient.rgb * albedo;
+
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i++) {
+        let light = lights.items[i];
+
+        var light_dir = -light.direction.xyz;
+        var attenuation = 1.0;
+        if light.kind != LIGHT_DIRECTIONAL {
+            let to_light = light.position.xyz - world_pos;
+            let dist = length(to_light);
+            light_dir = to_light / max(dist, 0.0001);
+            // Inverse square, smoothly reaching zero at the range.
+            let falloff = saturate(1.0 - pow(dist / light.range, 4.0));
+            attenuation = falloff * falloff / (dist * dist + 1.0);
+            if light.kind == LIGHT_SPOT {
+                let cos_angle = dot(-light_dir, light.direction.xyz);
+                attenuation *= smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+            }
+        }
+
+        let n_dot_l = dot(n, light_dir);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        if i == lights.shadow_light && receives_shadows != 0u {
+            attenuation *= shadow(world_pos, n);
+        }
+        let half_dir = normalize(light_dir + view_dir);
+        let specular = pow(max(dot(n, half_dir), 0.0), SHININESS) * SPECULAR_STRENGTH;
+        result += (albedo * n_dot_l + specular) * light.color.rgb * attenuation;
+    }
+
+    return result;
+}
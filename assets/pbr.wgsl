@@ -0,0 +1,221 @@
+#include "lighting.wgsl"
+
+// Group 0 is shared by all the scene materials, see `SceneBindings`.
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+}
+
+// Must match `ObjectUniform`.
+struct Object {
+    world: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    receives_shadows: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// Indexed by the instance index.
+@group(0) @binding(1)
+var<storage, read> objects: array<Object>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_pos: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) @interpolate(flat) receives_shadows: u32,
+}
+
+@vertex
+fn vs_main(in: VertexInput, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let object = objects[instance];
+    var out: VertexOutput;
+    let world_pos = object.world * vec4<f32>(in.position, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.world_pos = world_pos.xyz;
+    out.normal = (object.normal * vec4<f32>(in.normal, 0.0)).xyz;
+    out.tangent = vec4<f32>((object.world * vec4<f32>(in.tangent.xyz, 0.0)).xyz, in.tangent.w);
+    out.color = in.color;
+    out.clip_position = camera.view_proj * world_pos;
+    out.receives_shadows = object.receives_shadows;
+    return out;
+}
+
+// Factors multiply the texture values, as in glTF.
+struct Material {
+    base_color: vec4<f32>,
+    emissive: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> material: Material;
+
+// Fragments with lower alpha are discarded, set for `BlendMode::Cutout`.
+override alpha_cutoff: f32 = 0.0;
+
+@group(1) @binding(1)
+var t_base_color: texture_2d<f32>;
+
+@group(1) @binding(2)
+var s_base_color: sampler;
+
+// Roughness in G, metallic in B
+@group(1) @binding(3)
+var t_metallic_roughness: texture_2d<f32>;
+
+@group(1) @binding(4)
+var s_metallic_roughness: sampler;
+
+@group(1) @binding(5)
+var t_normal: texture_2d<f32>;
+
+@group(1) @binding(6)
+var s_normal: sampler;
+
+@group(1) @binding(7)
+var t_occlusion: texture_2d<f32>;
+
+@group(1) @binding(8)
+var s_occlusion: sampler;
+
+@group(1) @binding(9)
+var t_emissive: texture_2d<f32>;
+
+@group(1) @binding(10)
+var s_emissive: sampler;
+
+@group(2) @binding(0)
+var t_irradiance: texture_cube<f32>;
+
+@group(2) @binding(1)
+var s_irradiance: sampler;
+
+@group(2) @binding(2)
+var t_prefiltered: texture_cube<f32>;
+
+@group(2) @binding(3)
+var s_prefiltered: sampler;
+
+@group(2) @binding(4)
+var t_brdf_lut: texture_2d<f32>;
+
+@group(2) @binding(5)
+var s_brdf_lut: sampler;
+
+const PI: f32 = 3.14159265359;
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness * roughness * roughness * roughness;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    let gv = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let gl = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return gv * gl;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(saturate(1.0 - cos_theta), 5.0);
+}
+
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(saturate(1.0 - cos_theta), 5.0);
+}
+
+fn surface_normal(in: VertexOutput) -> vec3<f32> {
+    let n = normalize(in.normal);
+    let t = normalize(in.tangent.xyz - n * dot(n, in.tangent.xyz));
+    let b = cross(n, t) * in.tangent.w;
+    var tangent_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    tangent_normal = vec3<f32>(tangent_normal.xy * material.normal_scale, tangent_normal.z);
+    return normalize(mat3x3<f32>(t, b, n) * tangent_normal);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base_color = material.base_color * in.color
+        * textureSample(t_base_color, s_base_color, in.tex_coords);
+    if base_color.a < alpha_cutoff {
+        discard;
+    }
+    let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.tex_coords);
+    let metallic = saturate(material.metallic * metallic_roughness.b);
+    // Too low roughness makes the highlights disappear between pixels.
+    let roughness = clamp(material.roughness * metallic_roughness.g, 0.04, 1.0);
+    let occlusion = mix(1.0, textureSample(t_occlusion, s_occlusion, in.tex_coords).r, material.occlusion_strength);
+    let emissive = material.emissive * textureSample(t_emissive, s_emissive, in.tex_coords).rgb;
+
+    let n = surface_normal(in);
+    let v = normalize(lights.camera_pos.xyz - in.world_pos);
+    let n_dot_v = max(dot(n, v), 0.0001);
+    let albedo = base_color.rgb;
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+
+    var direct = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i++) {
+        let light = lights.items[i];
+
+        var l = -light.direction.xyz;
+        var attenuation = 1.0;
+        if light.kind != LIGHT_DIRECTIONAL {
+            let to_light = light.position.xyz - in.world_pos;
+            let dist = length(to_light);
+            l = to_light / max(dist, 0.0001);
+            // Inverse square, smoothly reaching zero at the range.
+            let falloff = saturate(1.0 - pow(dist / light.range, 4.0));
+            attenuation = falloff * falloff / (dist * dist + 1.0);
+            if light.kind == LIGHT_SPOT {
+                let cos_angle = dot(-l, light.direction.xyz);
+                attenuation *= smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+            }
+        }
+
+        let n_dot_l = dot(n, l);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        if i == lights.shadow_light && in.receives_shadows != 0u {
+            attenuation *= shadow(in.world_pos, normalize(in.normal));
+        }
+
+        let h = normalize(v + l);
+        let f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+        let specular = distribution_ggx(max(dot(n, h), 0.0), roughness)
+            * geometry_smith(n_dot_v, n_dot_l, roughness) * f / (4.0 * n_dot_v * n_dot_l + 0.0001);
+        let diffuse = (1.0 - f) * (1.0 - metallic) * albedo / PI;
+        direct += (diffuse + specular) * light.color.rgb * attenuation * n_dot_l;
+    }
+
+    // Split sum approximation
+    let f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    let diffuse = (1.0 - f) * (1.0 - metallic) * albedo
+        * textureSample(t_irradiance, s_irradiance, n).rgb;
+    let max_lod = f32(textureNumLevels(t_prefiltered) - 1u);
+    let prefiltered = textureSampleLevel(t_prefiltered, s_prefiltered, reflect(-v, n), roughness * max_lod).rgb;
+    let brdf = textureSample(t_brdf_lut, s_brdf_lut, vec2<f32>(n_dot_v, roughness)).rg;
+    let specular = prefiltered * (f * brdf.x + brdf.y);
+    let ambient = (diffuse + specular) * occlusion;
+
+    return vec4<f32>(direct + ambient + emissive, base_color.a);
+}
@@ -25,15 +25,310 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     return out;
 }
 
+struct Effect {
+    // Meaning depends on the effect, see the fragment entry points.
+    params: vec4<f32>,
+    texel_size: vec2<f32>,
+}
+
 @group(0) @binding(0)
-var texture: texture_2d<f32>;
+var<uniform> effect: Effect;
 
 @group(0) @binding(1)
-var texSampler: sampler;
+var src: texture_2d<f32>;
+
+@group(0) @binding(2)
+var src_sampler: sampler;
+
+// Second input, depends on the entry point. The source is bound again when it's unused.
+@group(0) @binding(3)
+var aux: texture_2d<f32>;
+
+@group(0) @binding(4)
+var aux_sampler: sampler;
+
+fn sample_src(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(src, src_sampler, uv, 0.0);
+}
+
+fn sample_aux(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(aux, aux_sampler, uv, 0.0);
+}
+
+fn luma(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+@fragment
+fn fs_copy(in: VertexOutput) -> @location(0) vec4<f32> {
+    return sample_src(in.tex_coords);
+}
+
+// params: radius, softness, strength
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let c = sample_src(in.tex_coords);
+    let radius = effect.params.x;
+    let d = length(in.tex_coords - vec2<f32>(0.5, 0.5));
+    let v = 1.0 - effect.params.z * smoothstep(radius - effect.params.y, radius, d);
+    return vec4<f32>(c.rgb * v, c.a);
+}
+
+// params: gamma
+@fragment
+fn fs_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    let c = sample_src(in.tex_coords);
+    return vec4<f32>(pow(max(c.rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / effect.params.x)), c.a);
+}
+
+// A 16x16x16 LUT in aux, as 16 slices of 16x16 laid out horizontally: red along x, green
+// along y, blue selecting the slice. LUTs are authored for display colors, so the lookup is
+// done gamma-encoded and the HDR part above 1 is kept as is. params: strength, LUT size
+@fragment
+fn fs_color_grading(in: VertexOutput) -> @location(0) vec4<f32> {
+    let c = sample_src(in.tex_coords);
+    let color = pow(clamp(c.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / 2.2));
+    let size = effect.params.y;
+
+    // Blue is interpolated manually between the two nearest slices
+    let slice = color.b * (size - 1.0);
+    let slice0 = floor(slice);
+    let slice1 = min(slice0 + 1.0, size - 1.0);
+    let xy = (color.rg * (size - 1.0) + 0.5) / vec2<f32>(size * size, size);
+    let graded0 = sample_aux(vec2<f32>(xy.x + slice0 / size, xy.y));
+    let graded1 = sample_aux(vec2<f32>(xy.x + slice1 / size, xy.y));
+    let graded = pow(mix(graded0.rgb, graded1.rgb, slice - slice0), vec3<f32>(2.2))
+        + max(c.rgb - vec3<f32>(1.0), vec3<f32>(0.0));
+
+    return vec4<f32>(mix(c.rgb, graded, effect.params.x), c.a);
+}
+
+// The classic low-quality FXAA: blurs along the edge direction estimated from the luma
+// of the diagonal neighbours.
+@fragment
+fn fs_fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.tex_coords;
+    let texel = effect.texel_size;
+
+    let c = sample_src(uv);
+    let luma_nw = luma(sample_src(uv + vec2<f32>(-1.0, -1.0) * texel).rgb);
+    let luma_ne = luma(sample_src(uv + vec2<f32>(1.0, -1.0) * texel).rgb);
+    let luma_sw = luma(sample_src(uv + vec2<f32>(-1.0, 1.0) * texel).rgb);
+    let luma_se = luma(sample_src(uv + vec2<f32>(1.0, 1.0) * texel).rgb);
+    let luma_m = luma(c.rgb);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * 0.125, 1.0 / 128.0);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-8.0), vec2<f32>(8.0)) * texel;
+
+    let rgb_a = 0.5 * (
+        sample_src(uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        sample_src(uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        sample_src(uv - dir * 0.5).rgb +
+        sample_src(uv + dir * 0.5).rgb
+    );
+    let luma_b = luma(rgb_b);
+
+    if luma_b < luma_min || luma_b > luma_max {
+        return vec4<f32>(rgb_a, c.a);
+    }
+    return vec4<f32>(rgb_b, c.a);
+}
+
+// Red and blue are shifted in opposite directions away from the center. params: strength
+@fragment
+fn fs_chromatic_aberration(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.tex_coords;
+    let offset = (uv - vec2<f32>(0.5, 0.5)) * effect.params.x;
+    let c = sample_src(uv);
+    return vec4<f32>(sample_src(uv + offset).r, c.g, sample_src(uv - offset).b, c.a);
+}
+
+// Bloom: the bright parts are downsampled into a chain of half-sized targets, which is then
+// upsampled back with each level added to the blurred one below it.
 
+// Renders into a half-sized target, keeping what's over the threshold with a soft knee.
+// params: threshold
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // Basic vignetting
-    var m = max(0.0, 1.0 - length(in.tex_coords - vec2<f32>(0.5, 0.5)) / 1.0);
-    return textureSample(texture, texSampler, in.tex_coords) * m;
+fn fs_bloom_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let c = downsample(in.tex_coords);
+    let threshold = effect.params.x;
+    let knee = threshold * 0.5;
+    let brightness = max(c.r, max(c.g, c.b));
+    var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-5);
+    let contribution = max(soft, brightness - threshold) / max(brightness, 1e-5);
+    return vec4<f32>(c * contribution, 1.0);
+}
+
+@fragment
+fn fs_bloom_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.tex_coords), 1.0);
+}
+
+// Tent filter over the smaller level in src, added to the same sized one in aux.
+// params: radius in texels of src
+@fragment
+fn fs_bloom_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.tex_coords;
+    let d = effect.texel_size * effect.params.x;
+    var sum = sample_src(uv).rgb * 4.0;
+    sum += (
+        sample_src(uv + vec2<f32>(-d.x, 0.0)).rgb + sample_src(uv + vec2<f32>(d.x, 0.0)).rgb +
+        sample_src(uv + vec2<f32>(0.0, -d.y)).rgb + sample_src(uv + vec2<f32>(0.0, d.y)).rgb
+    ) * 2.0;
+    sum += sample_src(uv + vec2<f32>(-d.x, -d.y)).rgb + sample_src(uv + vec2<f32>(d.x, -d.y)).rgb +
+        sample_src(uv + vec2<f32>(-d.x, d.y)).rgb + sample_src(uv + vec2<f32>(d.x, d.y)).rgb;
+    return vec4<f32>(sum / 16.0 + sample_aux(uv).rgb, 1.0);
+}
+
+// Adds the upsampled bloom in aux. params: intensity
+@fragment
+fn fs_bloom_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let c = sample_src(in.tex_coords);
+    return vec4<f32>(c.rgb + sample_aux(in.tex_coords).rgb * effect.params.x, c.a);
+}
+
+// Averages 4x4 source texels with 4 bilinear samples, for rendering into a half-sized target.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let d = effect.texel_size;
+    return (
+        sample_src(uv + vec2<f32>(-d.x, -d.y)).rgb + sample_src(uv + vec2<f32>(d.x, -d.y)).rgb +
+        sample_src(uv + vec2<f32>(-d.x, d.y)).rgb + sample_src(uv + vec2<f32>(d.x, d.y)).rgb
+    ) * 0.25;
+}
+
+// Auto exposure: the log luminance of the image is averaged by copying it down to 1x1, then
+// the adapted value moves towards the average a bit every frame.
+
+@fragment
+fn fs_log_luminance(in: VertexOutput) -> @location(0) vec4<f32> {
+    let l = log(max(luma(downsample(in.tex_coords)), 1e-4));
+    return vec4<f32>(l, 0.0, 0.0, 1.0);
+}
+
+// The average in src, the previously adapted value in aux. params: adaptation rate
+@fragment
+fn fs_adapt_luminance(in: VertexOutput) -> @location(0) vec4<f32> {
+    let current = sample_src(vec2<f32>(0.5, 0.5)).r;
+    let previous = sample_aux(vec2<f32>(0.5, 0.5)).r;
+    return vec4<f32>(mix(previous, current, effect.params.x), 0.0, 0.0, 1.0);
+}
+
+const TONEMAP_NONE: u32 = 0u;
+const TONEMAP_REINHARD: u32 = 1u;
+const TONEMAP_ACES: u32 = 2u;
+const TONEMAP_AGX: u32 = 3u;
+
+// The final pass onto the surface. The adapted log luminance is in aux with auto exposure.
+// params: exposure compensation in EV, operator, auto exposure
+@fragment
+fn fs_tonemap(in: VertexOutput) -> @location(0) vec4<f32> {
+    let c = sample_src(in.tex_coords);
+
+    var exposure = exp2(effect.params.x);
+    if effect.params.z > 0.5 {
+        // Middle grey for the average luminance
+        let average = exp(sample_aux(vec2<f32>(0.5, 0.5)).r);
+        exposure *= clamp(0.18 / average, 1.0 / 64.0, 64.0);
+    }
+    let color = c.rgb * exposure;
+
+    switch u32(effect.params.y) {
+        case TONEMAP_REINHARD: {
+            return vec4<f32>(color / (vec3<f32>(1.0) + color), c.a);
+        }
+        case TONEMAP_ACES: {
+            return vec4<f32>(aces(color), c.a);
+        }
+        case TONEMAP_AGX: {
+            return vec4<f32>(agx(color), c.a);
+        }
+        default: {
+            return vec4<f32>(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), c.a);
+        }
+    }
+}
+
+// Stephen Hill's fit of the ACES RRT and ODT.
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let input = mat3x3<f32>(
+        vec3<f32>(0.59719, 0.07600, 0.02840),
+        vec3<f32>(0.35458, 0.90834, 0.13383),
+        vec3<f32>(0.04823, 0.01566, 0.83777),
+    );
+    let output = mat3x3<f32>(
+        vec3<f32>(1.60475, -0.10208, -0.00327),
+        vec3<f32>(-0.53108, 1.10813, -0.07276),
+        vec3<f32>(-0.07367, -0.00605, 1.07602),
+    );
+    let v = input * color;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return clamp(output * (a / b), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// AgX with the default look, using the polynomial approximation of the contrast curve.
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var v = inset * color;
+    v = clamp(log2(max(v, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    v = (v - min_ev) / (max_ev - min_ev);
+
+    let x2 = v * v;
+    let x4 = x2 * x2;
+    v = 15.5 * x4 * x2 - 40.14 * x4 * v + 31.96 * x4 - 6.868 * x2 * v + 0.4298 * x2 + 0.1191 * v
+        - 0.00232;
+
+    // The curve outputs display encoded values, the surface expects linear ones.
+    return pow(max(outset * v, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+// Outline around the shapes in the aux mask, in their mask color, drawn over the source outside
+// of them. The mask is as big as the source. params: width in pixels
+@fragment
+fn fs_outline(in: VertexOutput) -> @location(0) vec4<f32> {
+    let c = sample_src(in.tex_coords);
+    if sample_aux(in.tex_coords).a > 0.0 {
+        return c;
+    }
+
+    // The most opaque mask texel within the width, partially covered ones soften the edge
+    let width = effect.params.x;
+    let radius = i32(ceil(width));
+    var outline = vec4<f32>(0.0);
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            if length(offset) > width {
+                continue;
+            }
+            let m = sample_aux(in.tex_coords + offset * effect.texel_size);
+            if m.a > outline.a {
+                outline = m;
+            }
+        }
+    }
+
+    return vec4<f32>(mix(c.rgb, outline.rgb, outline.a), c.a);
 }
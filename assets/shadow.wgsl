@@ -0,0 +1,8 @@
+// Light view-projection * world
+@group(0) @binding(0)
+var<uniform> matrix: mat4x4<f32>;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return matrix * vec4<f32>(position, 1.0);
+}
@@ -1,10 +1,12 @@
-struct Matrices {
-    view_mat: mat4x4<f32>,
-    proj_mat_inv: mat4x4<f32>,
+// Only the camera is used from the group shared by the scene materials, see `SceneBindings`.
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
 }
 
 @group(0) @binding(0)
-var<uniform> matrices: Matrices;
+var<uniform> camera: Camera;
 
 struct VertexInput {
     @location(0)
@@ -25,10 +27,10 @@ fn vs_main(in: VertexInput) -> VertexOutput {
 
     out.clip_position =  vec4<f32>(in.position, 1.0);
 
-    var pos_unprojected = matrices.proj_mat_inv * out.clip_position;
+    var pos_unprojected = camera.proj_inv * out.clip_position;
     // Couldn't pass a 3x3 matrix in the uniform so transforming it into 3x3 here.
     // Also just using the raw 4x4 view matrix does not work because of its position component, apparently.
-    var view_mat_inv = transpose(mat3x3<f32>(matrices.view_mat[0].xyz, matrices.view_mat[1].xyz, matrices.view_mat[2].xyz));
+    var view_mat_inv = transpose(mat3x3<f32>(camera.view[0].xyz, camera.view[1].xyz, camera.view[2].xyz));
     out.uv = view_mat_inv * pos_unprojected.xyz;
 
     return out;
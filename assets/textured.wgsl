@@ -1,26 +1,50 @@
-struct Matrices {
-    world: mat4x4<f32>,
+#include "lighting.wgsl"
+
+// Group 0 is shared by all the scene materials, see `SceneBindings`.
+struct Camera {
     view_proj: mat4x4<f32>,
-};
+    view: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+}
+
+// Must match `ObjectUniform`.
+struct Object {
+    world: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    receives_shadows: u32,
+}
 
 @group(0) @binding(0)
-var<uniform> matrices: Matrices;
+var<uniform> camera: Camera;
+
+// Indexed by the instance index.
+@group(0) @binding(1)
+var<storage, read> objects: array<Object>;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) world_pos: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) @interpolate(flat) receives_shadows: u32,
 }
 
 @vertex
-fn vs_main(in: VertexInput) -> VertexOutput {
+fn vs_main(in: VertexInput, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let object = objects[instance];
     var out: VertexOutput;
+    let world_pos = object.world * vec4<f32>(in.position, 1.0);
     out.tex_coords = in.tex_coords;
-    out.clip_position = matrices.view_proj * matrices.world * vec4<f32>(in.position, 1.0);
+    out.world_pos = world_pos.xyz;
+    out.normal = (object.normal * vec4<f32>(in.normal, 0.0)).xyz;
+    out.clip_position = camera.view_proj * world_pos;
+    out.receives_shadows = object.receives_shadows;
     return out;
 }
 
@@ -30,7 +54,23 @@ var t_diffuse: texture_2d<f32>;
 @group(1) @binding(1)
 var s_diffuse: sampler;
 
+struct UvTransform {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+};
+
+@group(2) @binding(0)
+var<uniform> uv_transform: UvTransform;
+
+// Fragments with lower alpha are discarded, set for `BlendMode::Cutout`.
+override alpha_cutoff: f32 = 0.0;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let uv = in.tex_coords * uv_transform.scale + uv_transform.offset;
+    let albedo = textureSample(t_diffuse, s_diffuse, uv);
+    if albedo.a < alpha_cutoff {
+        discard;
+    }
+    return vec4<f32>(shade(albedo.rgb, in.world_pos, in.normal, in.receives_shadows), albedo.a);
 }
@@ -1,48 +1,97 @@
 use futures_lite::future;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use winit::application::ApplicationHandler;
 use winit::dpi::PhysicalSize;
 use winit::event::{DeviceEvent, DeviceId, Event, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
 use winit::window::{Window, WindowId};
 
+use crate::file;
 use crate::frame_time::FrameTime;
 use crate::input::{Input, InputAction};
-use crate::render::Renderer;
+use crate::render::{Renderer, SurfaceSize};
 use crate::scene::Scene;
 use crate::scene::SceneCfg;
 use crate::state::AppState;
 
-#[derive(Default)]
+const WINDOW_SIZE: SurfaceSize = PhysicalSize {
+    width: 1900,
+    height: 1200,
+};
+// Lets physics and auto exposure settle before taking a headless screenshot.
+const HEADLESS_FRAMES: u32 = 120;
+const HEADLESS_DT: f32 = 1.0 / 60.0;
+
 pub struct App<'a> {
+    scene_file: &'a str,
     state: Option<AppState<'a>>,
     scene: Option<Scene>,
     frame_time: Option<FrameTime>,
+    // Nothing is updated or rendered while minimized
+    minimized: bool,
 }
 
-impl App<'_> {
+impl<'a> App<'a> {
+    pub fn new(scene_file: &'a str) -> Self {
+        Self {
+            scene_file,
+            state: None,
+            scene: None,
+            frame_time: None,
+            minimized: false,
+        }
+    }
+
     fn update_and_render(&mut self, event_loop: &ActiveEventLoop) {
-        // TODO Any better way?
-        let mut state = self.state.take().unwrap();
-        let mut scene = self.scene.take().unwrap();
+        if self.minimized {
+            return;
+        }
+
+        let state = self.state.as_mut().unwrap();
+        let scene = self.scene.as_mut().unwrap();
 
         if state.input.action_activated(InputAction::Quit) {
             event_loop.exit();
         }
 
-        let dt = self.frame_time.as_mut().unwrap().advance();
-
         state.renderer.resize(state.new_surface_size);
 
-        scene.update(dt, &state);
+        // The frame goes into the output target instead of the window to be read back
+        let screenshot = state.input.action_activated(InputAction::Screenshot);
+        if screenshot {
+            state.renderer.set_offscreen(true);
+        }
+
+        // Skipped frames keep the input, the new surface size and the elapsed time for the next
+        // one. The UI frame starts in the update, so it's skipped as well.
+        if let Err(e) = state.renderer.acquire_surface_texture() {
+            match e {
+                wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated => {
+                    state.renderer.reconfigure_surface()
+                }
+                wgpu::SurfaceError::OutOfMemory => {
+                    eprintln!("Exiting: {e}");
+                    event_loop.exit();
+                    return;
+                }
+                wgpu::SurfaceError::Timeout | wgpu::SurfaceError::Other => {}
+            }
+            state.window.as_ref().unwrap().request_redraw();
+            return;
+        }
+
+        let dt = self.frame_time.as_mut().unwrap().advance();
+        scene.update(dt, state);
         scene.render(&state.renderer);
+        if screenshot {
+            save_screenshot(&state.renderer);
+            state.renderer.set_offscreen(false);
+        }
 
         state.input.clear();
-        state.window.request_redraw();
+        state.window.as_ref().unwrap().request_redraw();
         state.new_surface_size = None;
-
-        self.state = Some(state);
-        self.scene = Some(scene);
     }
 }
 
@@ -57,32 +106,25 @@ impl ApplicationHandler for App<'_> {
             event_loop
                 .create_window(
                     Window::default_attributes()
-                        .with_inner_size(PhysicalSize {
-                            width: 1900,
-                            height: 1200,
-                        })
+                        .with_inner_size(WINDOW_SIZE)
                         .with_title("Demo"),
                 )
                 .unwrap(),
         );
         window.request_redraw();
 
-        let rr = future::block_on(Renderer::new(Arc::clone(&window)));
+        let cfg = load_scene_cfg(self.scene_file);
+        let rr = future::block_on(Renderer::new(Arc::clone(&window), msaa(&cfg)));
 
         let state = AppState {
-            window,
+            window: Some(window),
             renderer: rr,
             input: Input::new(),
             new_surface_size: None,
         };
 
         let mut scene = Scene::new(&state);
-        scene.insert_from_cfg(
-            &SceneCfg::from_yaml(&String::from_utf8_lossy(include_bytes!(
-                "../assets/scene.yml"
-            ))),
-            &state,
-        );
+        scene.insert_from_cfg(&cfg, &state);
 
         self.scene = Some(scene);
         self.frame_time = Some(FrameTime::new());
@@ -95,14 +137,27 @@ impl ApplicationHandler for App<'_> {
         window_id: WindowId,
         event: WindowEvent,
     ) {
-        if self.state.is_none() || window_id != self.state.as_ref().unwrap().window.id() {
+        if self
+            .state
+            .as_ref()
+            .is_none_or(|s| s.window.as_ref().unwrap().id() != window_id)
+        {
             return;
         }
 
         match &event {
             WindowEvent::RedrawRequested => self.update_and_render(event_loop),
             &WindowEvent::Resized(size) => {
-                self.state.as_mut().unwrap().new_surface_size = Some(size)
+                let state = self.state.as_mut().unwrap();
+                state.new_surface_size = Some(size);
+
+                // Redraws stop while minimized, the time in between doesn't count as a frame
+                let minimized = size.width == 0 || size.height == 0;
+                if self.minimized && !minimized {
+                    self.frame_time = Some(FrameTime::new());
+                    state.window.as_ref().unwrap().request_redraw();
+                }
+                self.minimized = minimized;
             }
             WindowEvent::CloseRequested => event_loop.exit(),
             _ => {}
@@ -128,3 +183,49 @@ impl ApplicationHandler for App<'_> {
             .handle_event(Event::DeviceEvent { device_id, event });
     }
 }
+
+// Renders the scene without a window and saves the last frame.
+pub fn render_headless(scene_file: &str, path: &str) {
+    let cfg = load_scene_cfg(scene_file);
+    let rr = future::block_on(Renderer::new_headless(WINDOW_SIZE, msaa(&cfg), false));
+
+    let state = AppState {
+        window: None,
+        renderer: rr,
+        input: Input::new(),
+        new_surface_size: None,
+    };
+
+    let mut scene = Scene::new(&state);
+    scene.insert_from_cfg(&cfg, &state);
+    for _ in 0..HEADLESS_FRAMES {
+        scene.update(HEADLESS_DT, &state);
+        scene.render(&state.renderer);
+    }
+
+    let output = state.renderer.output().unwrap();
+    match output.save_png(&state.renderer, path) {
+        Ok(()) => println!("{path}: rendered with {}", state.renderer.adapter_name),
+        Err(e) => eprintln!("{path}: {e}"),
+    }
+}
+
+fn load_scene_cfg(scene_file: &str) -> SceneCfg {
+    SceneCfg::from_yaml(&future::block_on(file::read_string_asset(scene_file)).unwrap())
+}
+
+fn msaa(cfg: &SceneCfg) -> u32 {
+    cfg.render.as_ref().and_then(|r| r.msaa).unwrap_or(4)
+}
+
+fn save_screenshot(rr: &Renderer) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let path = format!("screenshot-{secs}.png");
+    match rr.output().unwrap().save_png(rr, &path) {
+        Ok(()) => println!("Saved {path}"),
+        Err(e) => eprintln!("Failed to save {path}: {e}"),
+    }
+}
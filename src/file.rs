@@ -6,6 +6,34 @@ fn full_path(relative_path: &str) -> PathBuf {
     std::path::Path::new("./assets").join(relative_path)
 }
 
+fn full_cache_path(relative_path: &str) -> PathBuf {
+    std::path::Path::new("./cache").join(relative_path)
+}
+
+// Paths of all the files in the assets dir and its subdirs, relative to it.
+pub fn list_assets() -> Result<Vec<String>> {
+    fn visit(dir: &std::path::Path, prefix: &str, paths: &mut Vec<String>) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let Result::Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let path = format!("{prefix}{name}");
+            if entry.path().is_dir() {
+                visit(&entry.path(), &format!("{path}/"), paths)?;
+            } else {
+                paths.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut paths = Vec::new();
+    visit(&full_path(""), "", &mut paths)?;
+    paths.sort();
+    Ok(paths)
+}
+
 pub async fn read_binary_asset(file_path: &str) -> Result<Vec<u8>> {
     Ok(std::fs::read(full_path(file_path))?)
 }
@@ -13,3 +41,45 @@ pub async fn read_binary_asset(file_path: &str) -> Result<Vec<u8>> {
 pub async fn read_string_asset(file_path: &str) -> Result<String> {
     Ok(std::fs::read_to_string(full_path(file_path))?)
 }
+
+// Loads the document along with its buffers and decoded images, both embedded and external.
+pub async fn read_gltf_asset(
+    file_path: &str,
+) -> Result<(
+    gltf::Document,
+    Vec<gltf::buffer::Data>,
+    Vec<gltf::image::Data>,
+)> {
+    Ok(gltf::import(full_path(file_path))?)
+}
+
+pub async fn read_cache_file(file_path: &str) -> Result<Vec<u8>> {
+    Ok(std::fs::read(full_cache_path(file_path))?)
+}
+
+// Names of the files directly in the given cache dir, none if it doesn't exist yet.
+pub fn list_cache_files(dir: &str) -> Result<Vec<String>> {
+    let path = full_cache_path(dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = std::fs::read_dir(path)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+pub fn remove_cache_file(file_path: &str) -> Result<()> {
+    Ok(std::fs::remove_file(full_cache_path(file_path))?)
+}
+
+pub async fn write_cache_file(file_path: &str, data: &[u8]) -> Result<()> {
+    let path = full_cache_path(file_path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(std::fs::write(path, data)?)
+}
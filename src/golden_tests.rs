@@ -0,0 +1,228 @@
+// Golden-image tests: fixture scenes from `tests/golden` are rendered headlessly on the software
+// adapter from fixed camera positions and compared with the reference images next to them.
+// Failed views leave the actual and diff images in `target/golden`. Run with `GOLDEN_UPDATE=1`
+// to overwrite the references instead, after checking that the change is intended.
+use futures_lite::future;
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+use std::sync::Mutex;
+use winit::dpi::PhysicalSize;
+
+use crate::input::Input;
+use crate::math::Vec3;
+use crate::render::{Renderer, SurfaceSize};
+use crate::scene::{Scene, SceneCfg};
+use crate::state::AppState;
+
+const FIXTURES_DIR: &str = "tests/golden";
+const OUTPUT_DIR: &str = "target/golden";
+// Small, the software adapter is slow
+const SIZE: SurfaceSize = PhysicalSize {
+    width: 320,
+    height: 200,
+};
+// The post-processing targets are created during the first update
+const FRAMES: u32 = 2;
+const DT: f32 = 1.0 / 60.0;
+// Pixels differing more than this on the 0..1 scale of the perceptual color delta count as changed
+const PIXEL_THRESHOLD: f32 = 0.1;
+// Share of changed pixels that still matches, e.g. for another LLVM version of the adapter
+const MAX_CHANGED_SHARE: f32 = 0.005;
+
+// One renderer at a time, software adapters are slow enough without competing for the CPU
+static RENDERER_LOCK: Mutex<()> = Mutex::new(());
+
+struct View {
+    name: &'static str,
+    pos: Vec3,
+    target: Vec3,
+}
+
+impl View {
+    fn new(name: &'static str, pos: [f32; 3], target: [f32; 3]) -> Self {
+        Self {
+            name,
+            pos: pos.into(),
+            target: target.into(),
+        }
+    }
+}
+
+#[test]
+fn materials() {
+    check_views(
+        "materials.yml",
+        &[
+            View::new("materials_front", [0.0, 3.0, 8.0], [0.0, 1.0, 0.0]),
+            View::new("materials_above", [6.0, 8.0, 6.0], [0.0, 0.0, 0.0]),
+        ],
+    );
+}
+
+#[test]
+fn skybox() {
+    check_views(
+        "materials.yml",
+        &[View::new("skybox", [0.0, 3.0, 0.0], [10.0, 5.0, -10.0])],
+    );
+}
+
+#[test]
+fn float_skybox() {
+    check_views(
+        "float_skybox.yml",
+        &[View::new("float_skybox", [0.0, 1.0, 5.0], [0.0, 1.8, 0.0])],
+    );
+}
+
+#[test]
+fn hdr_environment() {
+    check_views(
+        "hdr_environment.yml",
+        &[View::new(
+            "hdr_environment",
+            [0.0, 1.0, 5.0],
+            [0.0, 1.8, 0.0],
+        )],
+    );
+}
+
+#[test]
+fn post_process() {
+    check_views(
+        "post_process.yml",
+        &[View::new("post_process", [0.0, 3.0, 7.0], [0.0, 1.5, 0.0])],
+    );
+}
+
+// Renders every view and compares them all before failing, so one run shows every broken view.
+fn check_views(fixture: &str, views: &[View]) {
+    let _lock = RENDERER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let yaml = std::fs::read_to_string(Path::new(FIXTURES_DIR).join(fixture)).unwrap();
+    let cfg = SceneCfg::from_yaml(&yaml);
+    let rr = future::block_on(Renderer::new_headless(SIZE, 1, true));
+    let state = AppState {
+        window: None,
+        renderer: rr,
+        input: Input::new(),
+        new_surface_size: None,
+    };
+
+    let mut scene = Scene::new(&state);
+    scene.insert_from_cfg(&cfg, &state);
+    scene.toggle_ui();
+
+    let update = std::env::var("GOLDEN_UPDATE").is_ok_and(|v| v == "1");
+    let mut failures = Vec::new();
+    for view in views {
+        scene.look_from(view.pos, view.target);
+        for _ in 0..FRAMES {
+            scene.update(DT, &state);
+            scene.render(&state.renderer);
+        }
+        let actual = state
+            .renderer
+            .output()
+            .unwrap()
+            .color_texture()
+            .read_rgba8(&state.renderer)
+            .unwrap();
+
+        let reference_path = Path::new(FIXTURES_DIR).join(format!("{}.png", view.name));
+        if update {
+            actual.save(&reference_path).unwrap();
+            continue;
+        }
+
+        if let Some(failure) = compare_with_reference(view.name, &actual, &reference_path) {
+            failures.push(failure);
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "Golden images don't match (rendered with {}):\n{}",
+        state.renderer.adapter_name,
+        failures.join("\n")
+    );
+}
+
+// Returns what's wrong, after saving the actual image and the diff if there's one.
+fn compare_with_reference(name: &str, actual: &RgbaImage, reference_path: &Path) -> Option<String> {
+    std::fs::create_dir_all(OUTPUT_DIR).unwrap();
+    let actual_path = Path::new(OUTPUT_DIR).join(format!("{name}.png"));
+
+    let Ok(reference) = image::open(reference_path) else {
+        actual.save(&actual_path).unwrap();
+        return Some(format!(
+            "{name}: no reference at {}, the rendered image is in {}. Run with GOLDEN_UPDATE=1 \
+             to make it the reference",
+            reference_path.display(),
+            actual_path.display()
+        ));
+    };
+    let reference = reference.to_rgba8();
+    if reference.dimensions() != actual.dimensions() {
+        actual.save(&actual_path).unwrap();
+        return Some(format!(
+            "{name}: {:?} rendered, {:?} expected",
+            actual.dimensions(),
+            reference.dimensions()
+        ));
+    }
+
+    let (changed, diff) = diff_images(actual, &reference);
+    let changed_share = changed as f32 / (actual.width() * actual.height()) as f32;
+    if changed_share <= MAX_CHANGED_SHARE {
+        return None;
+    }
+
+    let diff_path = Path::new(OUTPUT_DIR).join(format!("{name}-diff.png"));
+    actual.save(&actual_path).unwrap();
+    diff.save(&diff_path).unwrap();
+    Some(format!(
+        "{name}: {changed} pixels ({:.2}%) changed, see {} and {}",
+        changed_share * 100.0,
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+// Counts the changed pixels and marks them red over the faded grayscale reference.
+fn diff_images(actual: &RgbaImage, reference: &RgbaImage) -> (u32, RgbaImage) {
+    let mut changed = 0;
+    let diff = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let a = actual.get_pixel(x, y);
+        let r = reference.get_pixel(x, y);
+        if color_delta(a, r) > PIXEL_THRESHOLD {
+            changed += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let (luma, ..) = yiq(r);
+            let faded = (255.0 - (255.0 - luma * 255.0) * 0.1) as u8;
+            Rgba([faded, faded, faded, 255])
+        }
+    });
+    (changed, diff)
+}
+
+// Weighted distance in the YIQ color space, normalized to 0..1, as in "Measuring perceived color
+// difference using YIQ NTSC transmission color space in mobile applications" by Kotsarenko and
+// Ramos. Closer to how different the colors look than per-channel differences.
+fn color_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    const MAX_DELTA: f32 = 35215.0 / (255.0 * 255.0);
+    let (y1, i1, q1) = yiq(a);
+    let (y2, i2, q2) = yiq(b);
+    let delta = 0.5053 * (y1 - y2).powi(2) + 0.299 * (i1 - i2).powi(2) + 0.1957 * (q1 - q2).powi(2);
+    (delta / MAX_DELTA).sqrt()
+}
+
+fn yiq(c: &Rgba<u8>) -> (f32, f32, f32) {
+    let [r, g, b, _] = c.0.map(|v| v as f32 / 255.0);
+    (
+        r * 0.299 + g * 0.587 + b * 0.114,
+        r * 0.596 - g * 0.274 - b * 0.322,
+        r * 0.211 - g * 0.523 + b * 0.312,
+    )
+}
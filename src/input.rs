@@ -14,6 +14,13 @@ pub enum InputAction {
     ControlPlayer,
     Spawn,
     Grab,
+    Screenshot,
+    ToggleUi,
+    ToggleDebugDraw,
+    Select,
+    GizmoTranslate,
+    GizmoRotate,
+    GizmoScale,
 }
 
 #[derive(Eq, PartialEq, Hash, Copy, Clone)]
@@ -164,5 +171,12 @@ fn action_key(action: InputAction) -> Key {
         InputAction::ControlPlayer => Key::Keyboard(KeyCode::Tab),
         InputAction::Spawn => Key::Keyboard(KeyCode::KeyF),
         InputAction::Grab => Key::MouseButton(MouseButton::Left),
+        InputAction::Screenshot => Key::Keyboard(KeyCode::F12),
+        InputAction::ToggleUi => Key::Keyboard(KeyCode::F1),
+        InputAction::ToggleDebugDraw => Key::Keyboard(KeyCode::F2),
+        InputAction::Select => Key::MouseButton(MouseButton::Right),
+        InputAction::GizmoTranslate => Key::Keyboard(KeyCode::Digit1),
+        InputAction::GizmoRotate => Key::Keyboard(KeyCode::Digit2),
+        InputAction::GizmoScale => Key::Keyboard(KeyCode::Digit3),
     }
 }
@@ -1,9 +1,12 @@
 use crate::app::App;
+use futures_lite::future;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 mod app;
 mod file;
 mod frame_time;
+#[cfg(test)]
+mod golden_tests;
 mod input;
 mod math;
 mod physics;
@@ -16,16 +19,70 @@ mod window;
 // TODO First-person controller.
 // TODO Spawned boxes should be rotated based on the camera view.
 // TODO Dragging should maintain box rotation relative to the camera.
-// TODO Selected object highlighting.
-// TODO Gizmos (e.g. axes instead of a box representing the player's target).
 // TODO Switch to raw Vulkan and/or introduce it as a separate backend. wgpu has an unstable API.
 
 fn main() {
+    let mut scene_file = "scene.yml";
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(cmd) = args.get(1) {
+        match cmd.as_str() {
+            "bake-meshes" => return bake_meshes(),
+            // A lot of objects for comparing frame times.
+            "benchmark" => scene_file = "benchmark.yml",
+            // Without a window, e.g. `screenshot scene.yml out.png`.
+            "screenshot" => {
+                return app::render_headless(
+                    args.get(2).map_or(scene_file, |s| s),
+                    args.get(3).map_or("screenshot.png", |s| s),
+                );
+            }
+            _ => {
+                eprintln!(
+                    "Unknown command {cmd}, available commands: bake-meshes, benchmark, screenshot"
+                );
+                return;
+            }
+        }
+    }
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = App::default();
+    let mut app = App::new(scene_file);
     if let Err(e) = event_loop.run_app(&mut app) {
         eprintln!("Error: {e}");
     }
 }
+
+// Pre-builds the mesh cache for every mesh file in the assets dir and its subdirs, then removes
+// the entries that aren't used by any of them anymore. Nothing is removed if a mesh fails to
+// bake, its old entry may still be in use.
+fn bake_meshes() {
+    let paths = file::list_assets()
+        .unwrap()
+        .into_iter()
+        .filter(|p| p.ends_with(".obj") || p.ends_with(".gltf") || p.ends_with(".glb"));
+
+    let mut baked = Vec::new();
+    let mut failed = false;
+    for path in paths {
+        match future::block_on(render::mesh_cache::bake(&path)) {
+            Ok((cache_path, rebuilt)) => {
+                println!("{path}: {}", if rebuilt { "baked" } else { "up to date" });
+                baked.push(cache_path);
+            }
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        eprintln!("Some meshes failed to bake, keeping the stale cache entries");
+        return;
+    }
+    for path in render::mesh_cache::remove_stale(&baked).unwrap() {
+        println!("{path}: removed");
+    }
+}
@@ -3,9 +3,12 @@ use rapier3d::na::Point3;
 
 pub type Vec2 = na::Vector2<f32>;
 pub type Vec3 = na::Vector3<f32>;
+pub type Vec4 = na::Vector4<f32>;
+pub type Mat3 = na::Matrix3<f32>;
 pub type Mat4 = na::Matrix4<f32>;
 pub type Quat = na::Quaternion<f32>;
 pub type UnitQuat = na::UnitQuaternion<f32>;
+pub use rapier3d::na::Orthographic3;
 pub use rapier3d::na::Perspective3;
 pub use rapier3d::na::Rotation3;
 pub use rapier3d::na::Translation3;
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod physics;
 
 use rapier3d::prelude;
@@ -7,5 +8,7 @@ pub type RigidBodyBuilder = prelude::RigidBodyBuilder;
 pub type ColliderHandle = prelude::ColliderHandle;
 pub type RigidBodyHandle = prelude::RigidBodyHandle;
 pub type RigidBodyType = prelude::RigidBodyType;
+pub type SharedShape = prelude::SharedShape;
+pub type Isometry = prelude::Isometry<prelude::Real>;
 
 pub use physics::{Physics, RayCastResult};
@@ -69,6 +69,26 @@ impl Physics {
         self.colliders.get_mut(handle).unwrap()
     }
 
+    pub fn colliders(&self) -> impl Iterator<Item = &Collider> {
+        self.colliders.iter().map(|(_, c)| c)
+    }
+
+    // World space points and normals of the touching collider pairs. Normals point from the
+    // first collider of the pair to the second one.
+    pub fn contacts(&self) -> impl Iterator<Item = (Vec3, Vec3)> + '_ {
+        self.narrow_phase
+            .contact_pairs()
+            .filter(|pair| pair.has_any_active_contact)
+            .flat_map(|pair| &pair.manifolds)
+            .flat_map(|manifold| {
+                manifold
+                    .data
+                    .solver_contacts
+                    .iter()
+                    .map(|contact| (contact.point.coords, manifold.data.normal))
+            })
+    }
+
     pub fn move_character(
         &self,
         dt: f32,
@@ -0,0 +1,236 @@
+use crate::math::{to_point3, Mat4, Vec3, Vec4};
+
+use super::vertex::MeshVertex;
+
+// Assembles the vertices for an indexed triangle list, generating the attributes missing from
+// the source data.
+pub fn build_vertices(
+    positions: &[[f32; 3]],
+    uvs: Option<Vec<[f32; 2]>>,
+    normals: Option<Vec<[f32; 3]>>,
+    colors: Option<Vec<[f32; 4]>>,
+    indices: &[u32],
+) -> Vec<MeshVertex> {
+    let uvs = uvs.unwrap_or_else(|| vec![[0.0, 0.0]; positions.len()]);
+    let normals = normals.unwrap_or_else(|| generate_normals(positions, indices));
+    let tangents = generate_tangents(positions, &uvs, &normals, indices);
+
+    (0..positions.len())
+        .map(|i| MeshVertex {
+            position: positions[i],
+            uv: uvs[i],
+            normal: normals[i],
+            tangent: tangents[i],
+            color: colors.as_ref().map_or([1.0; 4], |c| c[i]),
+        })
+        .collect()
+}
+
+// Area-weighted average of the adjacent face normals. Vertices that aren't shared between
+// faces end up with flat normals.
+pub fn generate_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::zeros(); positions.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let [p0, p1, p2] = [0, 1, 2].map(|i| Vec3::from(positions[tri[i] as usize]));
+        // Not normalized on purpose, the length is proportional to the triangle area.
+        let n = (p1 - p0).cross(&(p2 - p0));
+        for &i in tri {
+            normals[i as usize] += n;
+        }
+    }
+
+    normals
+        .into_iter()
+        .map(|n| {
+            n.try_normalize(f32::EPSILON)
+                .unwrap_or(*Vec3::y_axis())
+                .into()
+        })
+        .collect()
+}
+
+// Per-vertex tangents from the UV layout, orthogonalized against the normals.
+pub fn generate_tangents(
+    positions: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    normals: &[[f32; 3]],
+    indices: &[u32],
+) -> Vec<[f32; 4]> {
+    let mut tangents = vec![Vec3::zeros(); positions.len()];
+    let mut bitangents = vec![Vec3::zeros(); positions.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let [p0, p1, p2] = [0, 1, 2].map(|i| Vec3::from(positions[tri[i] as usize]));
+        let [uv0, uv1, uv2] = [0, 1, 2].map(|i| uvs[tri[i] as usize]);
+
+        let (e1, e2) = (p1 - p0, p2 - p0);
+        let (du1, dv1) = (uv1[0] - uv0[0], uv1[1] - uv0[1]);
+        let (du2, dv2) = (uv2[0] - uv0[0], uv2[1] - uv0[1]);
+
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() < f32::EPSILON {
+            // Degenerate UVs (e.g. when the mesh has none), leaving it to the fallback below.
+            continue;
+        }
+
+        let t = (e1 * dv2 - e2 * dv1) / det;
+        let b = (e2 * du1 - e1 * du2) / det;
+        for &i in tri {
+            tangents[i as usize] += t;
+            bitangents[i as usize] += b;
+        }
+    }
+
+    (0..positions.len())
+        .map(|i| {
+            let n = Vec3::from(normals[i]);
+            let t = tangents[i] - n * n.dot(&tangents[i]);
+            let t = t
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(|| any_perpendicular(n));
+            let w = if n.cross(&t).dot(&bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [t.x, t.y, t.z, w]
+        })
+        .collect()
+}
+
+fn any_perpendicular(v: Vec3) -> Vec3 {
+    let axis = if v.x.abs() < 0.9 {
+        Vec3::x_axis()
+    } else {
+        Vec3::y_axis()
+    };
+    v.cross(&axis)
+        .try_normalize(f32::EPSILON)
+        .unwrap_or(*Vec3::x_axis())
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    // Degenerates into a point at the origin when there are no points.
+    pub fn from_points(points: impl Iterator<Item = [f32; 3]>) -> Self {
+        points
+            .map(Vec3::from)
+            .fold(None, |acc: Option<Self>, p| {
+                Some(acc.map_or(Self { min: p, max: p }, |b| Self {
+                    min: b.min.inf(&p),
+                    max: b.max.sup(&p),
+                }))
+            })
+            .unwrap_or(Self {
+                min: Vec3::zeros(),
+                max: Vec3::zeros(),
+            })
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    // Bounds of the box after transforming all its corners.
+    pub fn transformed(&self, m: &Mat4) -> Self {
+        Self::from_points((0..8).map(|i| {
+            let corner = Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            m.transform_point(&to_point3(corner)).coords.into()
+        }))
+    }
+}
+
+// Planes of a view frustum with normals pointing inside, as (normal, distance).
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    // Expects an OpenGL-style projection, i.e. before `WGPU_CONVERSION_MATRIX`.
+    pub fn from_view_proj(m: &Mat4) -> Self {
+        let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|i| m.row(i).transpose());
+        Self {
+            planes: [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2],
+        }
+    }
+
+    // Conservative, boxes near the frustum corners may pass without actually intersecting it.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|p| {
+            let n = p.xyz();
+            // The corner furthest along the plane normal
+            let corner = Vec3::new(
+                if n.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if n.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if n.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            n.dot(&corner) + p.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unit quad in the XY plane facing +Z, with u along +X and v along +Y.
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const UVS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+    const INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    fn assert_near(a: [f32; 4], b: [f32; 4]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn quad_normals() {
+        for n in generate_normals(&POSITIONS, &INDICES) {
+            assert_near([n[0], n[1], n[2], 0.0], [0.0, 0.0, 1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn quad_tangents() {
+        let normals = generate_normals(&POSITIONS, &INDICES);
+        for t in generate_tangents(&POSITIONS, &UVS, &normals, &INDICES) {
+            assert_near(t, [1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_handedness() {
+        let uvs = UVS.map(|[u, v]| [1.0 - u, v]);
+        let normals = generate_normals(&POSITIONS, &INDICES);
+        for t in generate_tangents(&POSITIONS, &uvs, &normals, &INDICES) {
+            assert_near(t, [-1.0, 0.0, 0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs_fall_back_to_a_perpendicular_tangent() {
+        let normals = generate_normals(&POSITIONS, &INDICES);
+        for t in generate_tangents(&POSITIONS, &[[0.0; 2]; 4], &normals, &INDICES) {
+            let t = Vec3::new(t[0], t[1], t[2]);
+            assert!((t.norm() - 1.0).abs() < 1e-5);
+            assert!(t.dot(&Vec3::z()).abs() < 1e-5);
+        }
+    }
+}
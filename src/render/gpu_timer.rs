@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::sync::mpsc;
+
+type MapReceiver = mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>;
+
+// Timestamp queries around render passes. The timestamps of a frame are read back
+// asynchronously, so the timings lag a few frames behind, and frames that end while an earlier
+// readback is still in flight aren't measured.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // Nanoseconds per tick
+    period: f32,
+    // Passes of the current frame, with two queries each
+    passes: RefCell<Vec<String>>,
+    // Passes being read back along with the mapping result
+    pending: RefCell<Option<(Vec<String>, MapReceiver)>>,
+    // Milliseconds per pass label of the last read back frame
+    timings: RefCell<Vec<(String, f32)>>,
+}
+
+impl GpuTimer {
+    const MAX_PASSES: u32 = 64;
+    const BUFFER_SIZE: u64 = Self::MAX_PASSES as u64 * 2 * size_of::<u64>() as u64;
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: None,
+            ty: wgpu::QueryType::Timestamp,
+            count: Self::MAX_PASSES * 2,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            passes: RefCell::new(Vec::new()),
+            pending: RefCell::new(None),
+            timings: RefCell::new(Vec::new()),
+        }
+    }
+
+    // None when the frame has run out of queries.
+    pub fn timestamp_writes(&self, label: &str) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let mut passes = self.passes.borrow_mut();
+        if passes.len() as u32 >= Self::MAX_PASSES {
+            return None;
+        }
+        let first = passes.len() as u32 * 2;
+        passes.push(label.to_owned());
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(first),
+            end_of_pass_write_index: Some(first + 1),
+        })
+    }
+
+    // Picks up the timings of an earlier frame if they're ready, then starts reading back the
+    // timestamps of the current one.
+    pub fn end_frame(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let passes = self.passes.take();
+
+        if let Some((pending_passes, receiver)) = self.pending.take() {
+            let _ = device.poll(wgpu::PollType::Poll);
+            match receiver.try_recv() {
+                Ok(Ok(())) => {
+                    self.read_timings(&pending_passes);
+                    self.readback_buffer.unmap();
+                }
+                Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => {}
+                Err(mpsc::TryRecvError::Empty) => {
+                    *self.pending.borrow_mut() = Some((pending_passes, receiver));
+                    return;
+                }
+            }
+        }
+
+        if passes.is_empty() {
+            return;
+        }
+
+        let queries = passes.len() as u32 * 2;
+        let size = queries as u64 * size_of::<u64>() as u64;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.resolve_query_set(&self.query_set, 0..queries, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        *self.pending.borrow_mut() = Some((passes, receiver));
+    }
+
+    // Passes sharing a label are summed up, in the order of their first pass.
+    pub fn timings(&self) -> Vec<(String, f32)> {
+        self.timings.borrow().clone()
+    }
+
+    fn read_timings(&self, passes: &[String]) {
+        let data = self.readback_buffer.slice(..).get_mapped_range();
+        let ticks: &[u64] = bytemuck::cast_slice(&data);
+
+        let mut timings = Vec::<(String, f32)>::new();
+        for (label, pair) in passes.iter().zip(ticks.chunks(2)) {
+            let ms = pair[1].saturating_sub(pair[0]) as f32 * self.period / 1_000_000.0;
+            match timings.iter_mut().find(|(l, _)| l == label) {
+                Some((_, total)) => *total += ms,
+                None => timings.push((label.clone(), ms)),
+            }
+        }
+        *self.timings.borrow_mut() = timings;
+    }
+}
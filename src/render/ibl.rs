@@ -0,0 +1,268 @@
+use crate::math::Vec3;
+
+use super::renderer::Renderer;
+use super::texture::Texture;
+
+// Image based lighting for the PBR materials, precomputed from an environment cubemap
+// with the `ibl.wgsl` shader.
+pub struct Ibl {
+    irradiance: Texture,
+    prefiltered: Texture,
+    brdf_lut: Texture,
+}
+
+impl Ibl {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+    const IRRADIANCE_SIZE: u32 = 32;
+    const PREFILTERED_SIZE: u32 = 128;
+    // Must match the shader.
+    const PREFILTERED_MIPS: u32 = 5;
+    const BRDF_LUT_SIZE: u32 = 256;
+
+    pub fn new(rr: &Renderer, env: &Texture, shader: &wgpu::ShaderModule) -> Self {
+        let env_group_layout = rr.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let env_group = rr.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &env_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(env.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(env.sampler()),
+                },
+            ],
+        });
+        let env_layout = rr.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&env_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mut encoder = rr.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        let irradiance = new_cube_texture(rr, Self::IRRADIANCE_SIZE, 1);
+        let pipeline = new_pipeline(rr, shader, Some(&env_layout), "fs_irradiance", Self::FORMAT);
+        for face in 0..6 {
+            let view = cube_face_view(&irradiance, face, 0);
+            let mut pass = begin_pass(&mut encoder, &view, wgpu::Color::BLACK);
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &env_group, &[]);
+            pass.draw(0..3, face..face + 1);
+        }
+
+        let prefiltered = new_cube_texture(rr, Self::PREFILTERED_SIZE, Self::PREFILTERED_MIPS);
+        let pipeline = new_pipeline(rr, shader, Some(&env_layout), "fs_prefilter", Self::FORMAT);
+        for mip in 0..Self::PREFILTERED_MIPS {
+            for face in 0..6 {
+                let view = cube_face_view(&prefiltered, face, mip);
+                let mut pass = begin_pass(&mut encoder, &view, wgpu::Color::BLACK);
+                pass.set_pipeline(&pipeline);
+                pass.set_bind_group(0, &env_group, &[]);
+                let instance = mip * 6 + face;
+                pass.draw(0..3, instance..instance + 1);
+            }
+        }
+
+        let brdf_lut = Self::render_brdf_lut(rr, shader, &mut encoder);
+
+        rr.queue().submit(Some(encoder.finish()));
+
+        Self {
+            irradiance: Self::cube_texture(rr, &irradiance),
+            prefiltered: Self::cube_texture(rr, &prefiltered),
+            brdf_lut,
+        }
+    }
+
+    // Lighting coming equally from all directions, for scenes without an environment map.
+    pub fn new_uniform(rr: &Renderer, color: Vec3, shader: &wgpu::ShaderModule) -> Self {
+        let color = wgpu::Color {
+            r: color.x as f64,
+            g: color.y as f64,
+            b: color.z as f64,
+            a: 1.0,
+        };
+
+        let mut encoder = rr.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        let cube = new_cube_texture(rr, 1, 1);
+        for face in 0..6 {
+            begin_pass(&mut encoder, &cube_face_view(&cube, face, 0), color);
+        }
+        let brdf_lut = Self::render_brdf_lut(rr, shader, &mut encoder);
+
+        rr.queue().submit(Some(encoder.finish()));
+
+        Self {
+            irradiance: Self::cube_texture(rr, &cube),
+            prefiltered: Self::cube_texture(rr, &cube),
+            brdf_lut,
+        }
+    }
+
+    pub fn irradiance(&self) -> &Texture {
+        &self.irradiance
+    }
+
+    pub fn prefiltered(&self) -> &Texture {
+        &self.prefiltered
+    }
+
+    pub fn brdf_lut(&self) -> &Texture {
+        &self.brdf_lut
+    }
+
+    fn render_brdf_lut(
+        rr: &Renderer,
+        shader: &wgpu::ShaderModule,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Texture {
+        let texture = rr.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: Self::BRDF_LUT_SIZE,
+                height: Self::BRDF_LUT_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::BRDF_LUT_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let pipeline = new_pipeline(rr, shader, None, "fs_brdf_lut", Self::BRDF_LUT_FORMAT);
+        {
+            let mut pass = begin_pass(encoder, &view, wgpu::Color::BLACK);
+            pass.set_pipeline(&pipeline);
+            pass.draw(0..3, 0..1);
+        }
+
+        Texture::from_view(
+            rr,
+            &texture,
+            view,
+            Self::BRDF_LUT_FORMAT,
+            wgpu::AddressMode::ClampToEdge,
+        )
+    }
+
+    fn cube_texture(rr: &Renderer, texture: &wgpu::Texture) -> Texture {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        Texture::from_view(
+            rr,
+            texture,
+            view,
+            Self::FORMAT,
+            wgpu::AddressMode::ClampToEdge,
+        )
+    }
+}
+
+fn new_cube_texture(rr: &Renderer, size: u32, mips: u32) -> wgpu::Texture {
+    rr.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count: mips,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: Ibl::FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    })
+}
+
+fn cube_face_view(texture: &wgpu::Texture, face: u32, mip: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        base_array_layer: face,
+        array_layer_count: Some(1),
+        base_mip_level: mip,
+        mip_level_count: Some(1),
+        ..Default::default()
+    })
+}
+
+fn new_pipeline(
+    rr: &Renderer,
+    shader: &wgpu::ShaderModule,
+    layout: Option<&wgpu::PipelineLayout>,
+    fs_entry_point: &str,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    rr.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout,
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(fs_entry_point),
+            targets: &[Some(format.into())],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn begin_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    clear_color: wgpu::Color,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear_color),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
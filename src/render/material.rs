@@ -1,39 +1,101 @@
 use super::vertex::Vertex;
-use super::{Renderer, Texture};
+use super::{Renderer, SamplerOptions, Texture};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlendMode {
+    Opaque,
+    // Straight (not premultiplied) alpha
+    AlphaBlend,
+    // Source scaled by its alpha
+    Additive,
+    // Opaque, discarding fragments with alpha below the cutoff. The shader must declare
+    // `override alpha_cutoff: f32`.
+    Cutout(f32),
+}
+
+impl BlendMode {
+    // Transparent materials don't write depth and have to be drawn back-to-front after the
+    // opaque ones.
+    pub fn is_transparent(&self) -> bool {
+        matches!(self, BlendMode::AlphaBlend | BlendMode::Additive)
+    }
+
+    fn blend_state(&self) -> wgpu::BlendState {
+        match self {
+            BlendMode::Opaque | BlendMode::Cutout(_) => wgpu::BlendState::REPLACE,
+            BlendMode::AlphaBlend => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        }
+    }
+}
 
 pub struct Material {
     pipeline: wgpu::RenderPipeline,
-    bind_groups: Vec<wgpu::BindGroup>,
-    uniform_bufs: Vec<wgpu::Buffer>,
+    // Groups without a bind group are set by the caller, see `MaterialBuilder::with_scene_bindings`.
+    bind_groups: Vec<Option<wgpu::BindGroup>>,
+    // The first uniform added, see `write_uniform`
+    uniform_buffer: Option<wgpu::Buffer>,
+    blend_mode: BlendMode,
+    wireframe: bool,
 }
 
 pub struct MaterialBuilder {
-    bind_groups: Vec<(wgpu::BindGroup, wgpu::BindGroupLayout)>,
-    uniform_bufs: Vec<wgpu::Buffer>,
+    bind_groups: Vec<(Option<wgpu::BindGroup>, wgpu::BindGroupLayout)>,
+    uniform_buffer: Option<wgpu::Buffer>,
     wireframe: bool,
     depth_enabled: bool,
+    depth_test: bool,
     depth_write: bool,
+    blend_mode: BlendMode,
+    sample_count: Option<u32>,
+    color_format: wgpu::TextureFormat,
+    fragment_entry_point: &'static str,
 }
 
 impl MaterialBuilder {
     pub fn new() -> Self {
         Self {
             bind_groups: Vec::new(),
-            uniform_bufs: Vec::new(),
+            uniform_buffer: None,
             wireframe: false,
             depth_enabled: true,
+            depth_test: true,
             depth_write: true,
+            blend_mode: BlendMode::Opaque,
+            sample_count: None,
+            color_format: Renderer::HDR_TEX_FORMAT,
+            fragment_entry_point: "fs_main",
         }
     }
 
-    pub fn with_2d_texture(self, rr: &Renderer, texture: &Texture) -> Self {
-        let (bind_group_layout, bind_group) =
-            rr.new_texture_bind_group(texture, wgpu::TextureViewDimension::D2);
+    // Overrides the texture's own sampler.
+    pub fn with_2d_texture_and_sampler(
+        self,
+        rr: &Renderer,
+        texture: &Texture,
+        sampler: &SamplerOptions,
+    ) -> Self {
+        let (bind_group_layout, bind_group) = rr.new_texture_bind_group(
+            texture,
+            &sampler.new_sampler(rr),
+            wgpu::TextureViewDimension::D2,
+        );
         Self {
             bind_groups: self
                 .bind_groups
                 .into_iter()
-                .chain([(bind_group, bind_group_layout)])
+                .chain([(Some(bind_group), bind_group_layout)])
                 .collect(),
             ..self
         }
@@ -41,28 +103,90 @@ impl MaterialBuilder {
 
     pub fn with_cube_texture(self, rr: &Renderer, texture: &Texture) -> Self {
         let (bind_group_layout, bind_group) =
-            rr.new_texture_bind_group(texture, wgpu::TextureViewDimension::Cube);
+            rr.new_texture_bind_group(texture, texture.sampler(), wgpu::TextureViewDimension::Cube);
         Self {
             bind_groups: self
                 .bind_groups
                 .into_iter()
-                .chain([(bind_group, bind_group_layout)])
+                .chain([(Some(bind_group), bind_group_layout)])
                 .collect(),
             ..self
         }
     }
 
     pub fn with_uniform(self, rr: &Renderer, uniform: impl bytemuck::NoUninit) -> Self {
-        let (bind_group_layout, bind_group, buf) =
+        let (bind_group_layout, bind_group, buffer) =
             rr.new_uniform_bind_group(bytemuck::cast_slice(&[uniform]));
 
         Self {
             bind_groups: self
                 .bind_groups
                 .into_iter()
-                .chain([(bind_group, bind_group_layout)])
+                .chain([(Some(bind_group), bind_group_layout)])
+                .collect(),
+            uniform_buffer: self.uniform_buffer.or(Some(buffer)),
+            ..self
+        }
+    }
+
+    // Must go first, the group is set when building render bundles.
+    pub fn with_scene_bindings(self, rr: &Renderer) -> Self {
+        Self {
+            bind_groups: self
+                .bind_groups
+                .into_iter()
+                .chain([(None, rr.scene_bind_group_layout().clone())])
+                .collect(),
+            ..self
+        }
+    }
+
+    // Each texture with its own sampler, at bindings 0, 2, 4...
+    pub fn with_textures(
+        self,
+        rr: &Renderer,
+        textures: &[(&Texture, wgpu::TextureViewDimension)],
+    ) -> Self {
+        let textures = textures
+            .iter()
+            .map(|(t, dim)| (*t, t.sampler(), *dim))
+            .collect::<Vec<_>>();
+        let (bind_group_layout, bind_group, _) =
+            rr.new_uniform_and_textures_bind_group(None, &textures);
+
+        Self {
+            bind_groups: self
+                .bind_groups
+                .into_iter()
+                .chain([(Some(bind_group), bind_group_layout)])
                 .collect(),
-            uniform_bufs: self.uniform_bufs.into_iter().chain([buf]).collect(),
+            ..self
+        }
+    }
+
+    // Uniform at binding 0, then the textures at bindings 1, 3, 5... all sharing one sampler.
+    pub fn with_uniform_and_2d_textures(
+        self,
+        rr: &Renderer,
+        uniform: impl bytemuck::NoUninit,
+        textures: &[&Texture],
+        sampler: &SamplerOptions,
+    ) -> Self {
+        let sampler = sampler.new_sampler(rr);
+        let textures = textures
+            .iter()
+            .map(|t| (*t, &sampler, wgpu::TextureViewDimension::D2))
+            .collect::<Vec<_>>();
+        let (bind_group_layout, bind_group, buffer) = rr
+            .new_uniform_and_textures_bind_group(Some(bytemuck::cast_slice(&[uniform])), &textures);
+
+        Self {
+            bind_groups: self
+                .bind_groups
+                .into_iter()
+                .chain([(Some(bind_group), bind_group_layout)])
+                .collect(),
+            uniform_buffer: self.uniform_buffer.or(buffer),
             ..self
         }
     }
@@ -78,6 +202,46 @@ impl MaterialBuilder {
         }
     }
 
+    // Without the test everything is drawn over what's already there, depth is still written.
+    pub fn depth_test(self, test: bool) -> Self {
+        Self {
+            depth_test: test,
+            ..self
+        }
+    }
+
+    // Transparent modes also turn off depth writes.
+    pub fn blend_mode(self, mode: BlendMode) -> Self {
+        Self {
+            blend_mode: mode,
+            ..self
+        }
+    }
+
+    // Must match the render target, defaults to the renderer's MSAA sample count.
+    pub fn sample_count(self, count: u32) -> Self {
+        Self {
+            sample_count: Some(count),
+            ..self
+        }
+    }
+
+    // Must match the render target, defaults to the HDR format of the targets.
+    pub fn color_format(self, format: wgpu::TextureFormat) -> Self {
+        Self {
+            color_format: format,
+            ..self
+        }
+    }
+
+    // For shaders sharing the vertex stage between several fragment entry points.
+    pub fn fragment_entry_point(self, entry_point: &'static str) -> Self {
+        Self {
+            fragment_entry_point: entry_point,
+            ..self
+        }
+    }
+
     pub fn build<V: Vertex>(self, rr: &Renderer, shader: &wgpu::ShaderModule) -> Material {
         let (bind_groups, bind_group_layouts): (Vec<_>, Vec<_>) =
             self.bind_groups.into_iter().unzip();
@@ -88,6 +252,10 @@ impl MaterialBuilder {
             push_constant_ranges: &[],
         });
 
+        let constants = match self.blend_mode {
+            BlendMode::Cutout(cutoff) => vec![("alpha_cutoff", cutoff as f64)],
+            _ => Vec::new(),
+        };
         let pipeline = rr.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: None,
             layout: Some(&layout),
@@ -99,11 +267,14 @@ impl MaterialBuilder {
             },
             fragment: Some(wgpu::FragmentState {
                 module: shader,
-                entry_point: Some("fs_main"),
-                compilation_options: Default::default(),
+                entry_point: Some(self.fragment_entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: rr.surface_texture_format(),
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    format: self.color_format,
+                    blend: Some(self.blend_mode.blend_state()),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -116,7 +287,10 @@ impl MaterialBuilder {
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
                 cull_mode: None,
-                polygon_mode: if self.wireframe {
+                // Line lists are drawn as lines regardless, when the feature is missing
+                polygon_mode: if self.wireframe
+                    && rr.features().contains(wgpu::Features::POLYGON_MODE_LINE)
+                {
                     wgpu::PolygonMode::Line
                 } else {
                     wgpu::PolygonMode::Fill
@@ -127,8 +301,12 @@ impl MaterialBuilder {
             depth_stencil: if self.depth_enabled {
                 Some(wgpu::DepthStencilState {
                     format: Renderer::DEPTH_TEX_FORMAT,
-                    depth_write_enabled: self.depth_write,
-                    depth_compare: wgpu::CompareFunction::Less,
+                    depth_write_enabled: self.depth_write && !self.blend_mode.is_transparent(),
+                    depth_compare: if self.depth_test {
+                        wgpu::CompareFunction::Less
+                    } else {
+                        wgpu::CompareFunction::Always
+                    },
                     stencil: wgpu::StencilState::default(),
                     bias: wgpu::DepthBiasState::default(),
                 })
@@ -136,7 +314,7 @@ impl MaterialBuilder {
                 None
             },
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: self.sample_count.unwrap_or(rr.sample_count()),
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -147,24 +325,49 @@ impl MaterialBuilder {
         Material {
             pipeline,
             bind_groups,
-            uniform_bufs: self.uniform_bufs,
+            uniform_buffer: self.uniform_buffer,
+            blend_mode: self.blend_mode,
+            wireframe: self.wireframe,
         }
     }
 }
 
 impl Material {
-    pub fn update_buffer(&self, rr: &Renderer, buf_idx: usize, data: impl bytemuck::NoUninit) {
-        rr.queue().write_buffer(
-            &self.uniform_bufs[buf_idx],
-            0,
-            bytemuck::cast_slice(&[data]),
-        );
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    // Drawn as lines rather than triangles.
+    pub fn is_wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    // Set by `apply`, without the scene group.
+    pub fn bind_group_count(&self) -> u32 {
+        self.bind_groups.iter().flatten().count() as u32
+    }
+
+    pub fn uses_scene_bindings(&self) -> bool {
+        self.bind_groups.first().is_some_and(|g| g.is_none())
+    }
+
+    // Replaces the contents of the first uniform in place, e.g. for tweaking parameters without
+    // rebuilding the material and the bundles drawing it. Must be of the type it was built with.
+    pub fn write_uniform(&self, rr: &Renderer, uniform: impl bytemuck::NoUninit) {
+        let buffer = self
+            .uniform_buffer
+            .as_ref()
+            .expect("Material without uniforms");
+        rr.queue()
+            .write_buffer(buffer, 0, bytemuck::cast_slice(&[uniform]));
     }
 
     pub fn apply<'a>(&'a self, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
         encoder.set_pipeline(&self.pipeline);
         for (idx, group) in self.bind_groups.iter().enumerate() {
-            encoder.set_bind_group(idx as _, group, &[]);
+            if let Some(group) = group {
+                encoder.set_bind_group(idx as _, group, &[]);
+            }
         }
     }
 }
@@ -1,18 +1,23 @@
-use std::io::{BufReader, Cursor};
+use std::any::TypeId;
+use std::ops::Range;
 use wgpu::util::DeviceExt;
 
-use crate::file;
+use crate::math::Vec3;
 
-use super::vertex::{PositionUvNormalVertex, PositionUvVertex};
+use super::geometry::Aabb;
+use super::mesh_data::{MeshData, MeshMaterial};
+use super::vertex::{MeshVertex, PositionUvVertex};
 
 struct MeshPart {
+    vertex_type: TypeId,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
+    material: Option<MeshMaterial>,
 }
 
 impl MeshPart {
-    fn from_data<T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable>(
+    fn from_data<T: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable + 'static>(
         device: &wgpu::Device,
         vertices: &[T],
         indices: &[u32],
@@ -30,15 +35,19 @@ impl MeshPart {
         });
 
         Self {
+            vertex_type: TypeId::of::<T>(),
             vertex_buffer,
             index_buffer,
             num_indices: indices.len() as u32,
+            material: None,
         }
     }
 }
 
 pub struct Mesh {
     parts: Vec<MeshPart>,
+    // In the mesh space, covering all the parts.
+    bounds: Aabb,
 }
 
 impl Mesh {
@@ -46,6 +55,25 @@ impl Mesh {
         self.parts.len() as u32
     }
 
+    pub fn part_index_count(&self, part: u32) -> u32 {
+        self.parts[part as usize].num_indices
+    }
+
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    // Whether all the parts use the given vertex format, e.g. to check they fit a pipeline.
+    pub fn has_vertex_type<T: 'static>(&self) -> bool {
+        self.parts
+            .iter()
+            .all(|p| p.vertex_type == TypeId::of::<T>())
+    }
+
+    pub fn part_material(&self, part: u32) -> Option<&MeshMaterial> {
+        self.parts[part as usize].material.as_ref()
+    }
+
     pub fn new_quad(device: &wgpu::Device) -> Self {
         Self {
             parts: vec![MeshPart::from_data(
@@ -74,6 +102,10 @@ impl Mesh {
                 ],
                 &[0, 1, 2, 0, 2, 3],
             )],
+            bounds: Aabb {
+                min: Vec3::new(-1.0, -1.0, 0.0),
+                max: Vec3::new(1.0, 1.0, 0.0),
+            },
         }
     }
 
@@ -84,25 +116,21 @@ impl Mesh {
                 MeshPart::from_data(
                     device,
                     &[
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.0, 0.0, 0.0],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [1.0, 0.0, 0.0],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.9, 0.1, 0.0],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.9, 0.0, 0.1],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
                     ],
                     &[0, 1, 1, 2, 1, 3],
@@ -110,25 +138,21 @@ impl Mesh {
                 MeshPart::from_data(
                     device,
                     &[
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.0, 0.0, 0.0],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.0, 1.0, 0.0],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.1, 0.9, 0.0],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.0, 0.9, 0.1],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
                     ],
                     &[0, 1, 1, 2, 1, 3],
@@ -136,82 +160,58 @@ impl Mesh {
                 MeshPart::from_data(
                     device,
                     &[
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.0, 0.0, 0.0],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.0, 0.0, 1.0],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.1, 0.0, 0.9],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
-                        PositionUvNormalVertex {
+                        MeshVertex {
                             position: [0.0, 0.1, 0.9],
-                            uv: Default::default(),     // unused
-                            normal: Default::default(), // unused
+                            ..Default::default()
                         },
                     ],
                     &[0, 1, 1, 2, 1, 3],
                 ),
             ],
+            bounds: Aabb {
+                min: Vec3::zeros(),
+                max: Vec3::from_element(1.0),
+            },
         }
     }
 
-    pub async fn from_data(device: &wgpu::Device, data: &str) -> Mesh {
-        let cursor = futures_lite::io::Cursor::new(data);
-        let mut reader = futures_lite::io::BufReader::new(cursor);
-
-        let (meshes, _) = tobj::futures::load_obj_buf(
-            &mut reader,
-            &tobj::LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            },
-            |p| async move {
-                let mat_text = file::read_string_asset(p.to_str().unwrap()).await.unwrap();
-                tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
-            },
-        )
-        .await
-        .unwrap();
-
-        let parts = meshes
-            .into_iter()
-            .map(|m| {
-                let vertices = (0..m.mesh.positions.len() / 3)
-                    .map(|i| PositionUvNormalVertex {
-                        position: [
-                            m.mesh.positions[i * 3],
-                            m.mesh.positions[i * 3 + 1],
-                            m.mesh.positions[i * 3 + 2],
-                        ],
-                        uv: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                        normal: [
-                            m.mesh.normals[i * 3],
-                            m.mesh.normals[i * 3 + 1],
-                            m.mesh.normals[i * 3 + 2],
-                        ],
-                    })
-                    .collect::<Vec<_>>();
-
-                MeshPart::from_data(device, &vertices, &m.mesh.indices)
+    pub fn new(device: &wgpu::Device, data: &MeshData) -> Self {
+        let parts = data
+            .parts
+            .iter()
+            .map(|p| MeshPart {
+                material: p.material.clone(),
+                ..MeshPart::from_data(device, &p.vertices, &p.indices)
             })
-            .collect::<Vec<_>>();
+            .collect();
 
-        Mesh { parts }
+        Self {
+            parts,
+            bounds: data.bounds,
+        }
     }
 
-    pub fn draw_part<'a>(&'a self, part: u32, encoder: &mut wgpu::RenderBundleEncoder<'a>) {
+    pub fn draw_part<'a>(
+        &'a self,
+        part: u32,
+        instances: Range<u32>,
+        encoder: &mut wgpu::RenderBundleEncoder<'a>,
+    ) {
         let part = &self.parts[part as usize];
         encoder.set_vertex_buffer(0, part.vertex_buffer.slice(..));
         encoder.set_index_buffer(part.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        encoder.draw_indexed(0..part.num_indices, 0, 0..1);
+        encoder.draw_indexed(0..part.num_indices, 0, instances);
     }
 }
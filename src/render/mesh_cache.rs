@@ -0,0 +1,375 @@
+use anyhow::*;
+
+use crate::file;
+use crate::math::Vec3;
+
+use super::geometry::Aabb;
+use super::mesh_data::{MeshData, MeshMaterial, MeshPartData};
+use super::vertex::{MeshVertex, Vertex};
+
+// Binary mesh format. Numbers are little-endian, strings are a u32 byte length followed by UTF-8.
+//   magic "DMSH", version: u32
+//   vertex layout: byte length: u32, stride: u32, attribute count: u32,
+//     then per attribute: shader location: u32, offset: u32, format name: string
+//   dependencies, i.e. other files the mesh was built from: count: u32,
+//     then per file: path relative to the assets dir: string, presence: u8, hash: u64
+//   bounds: min: [f32; 3], max: [f32; 3]
+//   parts: count: u32, then per part:
+//     material presence: u8, name: string, diffuse color presence: u8, diffuse color: [f32; 3],
+//     diffuse texture presence: u8, diffuse texture: string
+//     vertex count: u32, index count: u32, vertices: [MeshVertex], indices: [u32]
+//     (vertices and indices are dumped from memory as is, which is little-endian on our targets)
+// Cache files are named after the hash of the source file, so editing the source simply leads
+// to a new cache entry. The old ones are removed by `remove_stale`.
+const MAGIC: &[u8; 4] = b"DMSH";
+const VERSION: u32 = 2;
+const DIR: &str = "meshes";
+
+// Loads the mesh via the cache, rebuilding the cache entry when it's missing or stale.
+pub async fn load(path: &str) -> Result<MeshData> {
+    let src = file::read_binary_asset(path).await?;
+    Ok(load_or_rebuild(path, &src).await?.0)
+}
+
+// Makes sure the cache entry is valid without uploading anything. Returns the path of the entry
+// and whether it was rebuilt.
+pub async fn bake(path: &str) -> Result<(String, bool)> {
+    let src = file::read_binary_asset(path).await?;
+    let rebuilt = load_or_rebuild(path, &src).await?.1;
+    Ok((cache_path(&src), rebuilt))
+}
+
+// Deletes the cache entries other than the given ones, returning the deleted paths.
+pub fn remove_stale(keep: &[String]) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    for name in file::list_cache_files(DIR)? {
+        let path = format!("{DIR}/{name}");
+        if !keep.contains(&path) {
+            file::remove_cache_file(&path)?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+fn cache_path(src: &[u8]) -> String {
+    format!("{DIR}/{:016x}.mesh", hash(src))
+}
+
+async fn load_or_rebuild(path: &str, src: &[u8]) -> Result<(MeshData, bool)> {
+    let cache_path = cache_path(src);
+
+    if let Result::Ok(cached) = file::read_cache_file(&cache_path).await {
+        match decode(&cached).await {
+            Result::Ok(Some(data)) => return Ok((data, false)),
+            Result::Ok(None) => (),
+            Err(e) => eprintln!("Ignoring broken mesh cache {cache_path}: {e}"),
+        }
+    }
+
+    let (data, deps) = MeshData::from_file(path, src).await?;
+    let mut dep_hashes = Vec::with_capacity(deps.len());
+    for dep in deps {
+        // Missing dependencies (e.g. a .mtl) are not fatal for the mesh, they're recorded as
+        // missing so that the entry gets outdated once they appear.
+        let dep_hash = match file::read_binary_asset(&dep).await {
+            Result::Ok(dep_data) => Some(hash(&dep_data)),
+            Err(e) => {
+                eprintln!("{path} references missing {dep}: {e}");
+                None
+            }
+        };
+        dep_hashes.push((dep, dep_hash));
+    }
+
+    if let Err(e) = file::write_cache_file(&cache_path, &encode(&data, &dep_hashes)).await {
+        eprintln!("Unable to write mesh cache {cache_path}: {e}");
+    }
+
+    Ok((data, true))
+}
+
+fn encode(data: &MeshData, deps: &[(String, Option<u64>)]) -> Vec<u8> {
+    let mut w = Writer(Vec::new());
+    w.0.extend_from_slice(MAGIC);
+    w.u32(VERSION);
+
+    let layout = vertex_layout_descriptor();
+    w.u32(layout.len() as u32);
+    w.0.extend_from_slice(&layout);
+
+    w.u32(deps.len() as u32);
+    for (path, hash) in deps {
+        w.string(path);
+        w.u8(hash.is_some() as u8);
+        w.u64(hash.unwrap_or_default());
+    }
+
+    w.vec3(&data.bounds.min);
+    w.vec3(&data.bounds.max);
+
+    w.u32(data.parts.len() as u32);
+    for part in &data.parts {
+        w.u8(part.material.is_some() as u8);
+        if let Some(mat) = &part.material {
+            w.string(&mat.name);
+            w.u8(mat.diffuse_color.is_some() as u8);
+            w.vec3(&Vec3::from(mat.diffuse_color.unwrap_or_default()));
+            w.u8(mat.diffuse_texture.is_some() as u8);
+            w.string(mat.diffuse_texture.as_deref().unwrap_or_default());
+        }
+        w.u32(part.vertices.len() as u32);
+        w.u32(part.indices.len() as u32);
+        w.0.extend_from_slice(bytemuck::cast_slice(&part.vertices));
+        w.0.extend_from_slice(bytemuck::cast_slice(&part.indices));
+    }
+
+    w.0
+}
+
+// None means the cache entry is valid but outdated.
+async fn decode(bytes: &[u8]) -> Result<Option<MeshData>> {
+    let mut r = Reader { bytes, pos: 0 };
+    if r.bytes(MAGIC.len())? != MAGIC {
+        bail!("Not a mesh cache file");
+    }
+    if r.u32()? != VERSION {
+        return Ok(None);
+    }
+
+    let layout_len = r.u32()? as usize;
+    if r.bytes(layout_len)? != vertex_layout_descriptor() {
+        return Ok(None);
+    }
+
+    for _ in 0..r.u32()? {
+        let path = r.string()?;
+        let present = r.u8()? != 0;
+        let expected_hash = r.u64()?;
+        let actual_hash = file::read_binary_asset(&path).await.ok().map(|d| hash(&d));
+        if actual_hash != present.then_some(expected_hash) {
+            return Ok(None);
+        }
+    }
+
+    let bounds = Aabb {
+        min: r.vec3()?,
+        max: r.vec3()?,
+    };
+
+    let parts_count = r.u32()?;
+    let mut parts = Vec::with_capacity(parts_count as usize);
+    for _ in 0..parts_count {
+        let material = if r.u8()? != 0 {
+            let name = r.string()?;
+            let has_color = r.u8()? != 0;
+            let color = r.vec3()?;
+            let has_texture = r.u8()? != 0;
+            let texture = r.string()?;
+            Some(MeshMaterial {
+                name,
+                diffuse_color: has_color.then_some(color.into()),
+                diffuse_texture: has_texture.then_some(texture),
+            })
+        } else {
+            None
+        };
+
+        let vertex_count = r.u32()? as usize;
+        let index_count = r.u32()? as usize;
+        let vertices =
+            bytemuck::pod_collect_to_vec(r.bytes(vertex_count * size_of::<MeshVertex>())?);
+        let indices = bytemuck::pod_collect_to_vec(r.bytes(index_count * size_of::<u32>())?);
+
+        parts.push(MeshPartData {
+            vertices,
+            indices,
+            material,
+        });
+    }
+
+    Ok(Some(MeshData { parts, bounds }))
+}
+
+// Any change to `MeshVertex` changes this and invalidates existing cache entries.
+fn vertex_layout_descriptor() -> Vec<u8> {
+    let layout = MeshVertex::buffer_layout();
+    let mut w = Writer(Vec::new());
+    w.u32(layout.array_stride as u32);
+    w.u32(layout.attributes.len() as u32);
+    for attr in layout.attributes {
+        w.u32(attr.shader_location);
+        w.u32(attr.offset as u32);
+        w.string(&format!("{:?}", attr.format));
+    }
+    w.0
+}
+
+// FNV-1a, unlike std's hasher it's guaranteed to be stable between builds.
+fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn vec3(&mut self, v: &Vec3) {
+        for c in v.iter() {
+            self.0.extend_from_slice(&c.to_le_bytes());
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.0.extend_from_slice(s.as_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let res = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("Unexpected end of mesh cache file"))?;
+        self.pos += len;
+        Ok(res)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.bytes(len)?.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future;
+
+    use super::*;
+
+    fn mesh() -> MeshData {
+        let vertices = (0..3)
+            .map(|i| MeshVertex {
+                position: [i as f32, 1.0, 2.0],
+                uv: [0.5, i as f32],
+                ..Default::default()
+            })
+            .collect();
+        MeshData::new(vec![
+            MeshPartData {
+                vertices,
+                indices: vec![0, 1, 2],
+                material: Some(MeshMaterial {
+                    name: "red".to_string(),
+                    diffuse_color: Some([1.0, 0.0, 0.0]),
+                    diffuse_texture: Some("textures/red.png".to_string()),
+                }),
+            },
+            MeshPartData {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+                material: None,
+            },
+        ])
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = mesh();
+        let decoded = future::block_on(decode(&encode(&data, &[])))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(decoded.bounds.min, data.bounds.min);
+        assert_eq!(decoded.bounds.max, data.bounds.max);
+        assert_eq!(decoded.parts.len(), data.parts.len());
+        for (a, b) in decoded.parts.iter().zip(&data.parts) {
+            assert_eq!(
+                bytemuck::cast_slice::<_, u8>(&a.vertices),
+                bytemuck::cast_slice::<_, u8>(&b.vertices)
+            );
+            assert_eq!(a.indices, b.indices);
+            assert_eq!(format!("{:?}", a.material), format!("{:?}", b.material));
+        }
+    }
+
+    #[test]
+    fn truncated_file_is_rejected() {
+        let bytes = encode(&mesh(), &[]);
+        for len in [0, 3, 6, bytes.len() / 2, bytes.len() - 1] {
+            assert!(future::block_on(decode(&bytes[..len])).is_err());
+        }
+    }
+
+    #[test]
+    fn other_version_is_outdated() {
+        let mut bytes = encode(&mesh(), &[]);
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(future::block_on(decode(&bytes)).unwrap().is_none());
+    }
+
+    #[test]
+    fn dependencies_are_checked() {
+        let cube = std::fs::read("assets/cube.obj").unwrap();
+        let is_valid = |deps: &[(String, Option<u64>)]| {
+            future::block_on(decode(&encode(&mesh(), deps)))
+                .unwrap()
+                .is_some()
+        };
+
+        assert!(is_valid(&[("cube.obj".to_string(), Some(hash(&cube)))]));
+        assert!(!is_valid(&[(
+            "cube.obj".to_string(),
+            Some(hash(&cube) + 1)
+        )]));
+        assert!(!is_valid(&[("cube.obj".to_string(), None)]));
+        assert!(is_valid(&[("missing.mtl".to_string(), None)]));
+        assert!(!is_valid(&[("missing.mtl".to_string(), Some(0))]));
+    }
+
+    #[test]
+    fn other_magic_is_rejected() {
+        let mut bytes = encode(&mesh(), &[]);
+        bytes[0] = b'X';
+        assert!(future::block_on(decode(&bytes)).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use std::cell::RefCell;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+
+use anyhow::*;
+
+use crate::file;
+
+use super::geometry::{self, Aabb};
+use super::vertex::MeshVertex;
+
+// Material description that comes with the mesh file, e.g. from an OBJ's .mtl.
+#[derive(Clone, Debug)]
+pub struct MeshMaterial {
+    pub name: String,
+    pub diffuse_color: Option<[f32; 3]>,
+    // Path relative to the assets dir, resolved from the one in the .mtl
+    pub diffuse_texture: Option<String>,
+}
+
+pub struct MeshPartData {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    pub material: Option<MeshMaterial>,
+}
+
+// CPU side of a mesh, either parsed from a source file or read from the mesh cache.
+pub struct MeshData {
+    pub parts: Vec<MeshPartData>,
+    pub bounds: Aabb,
+}
+
+impl MeshData {
+    pub fn new(parts: Vec<MeshPartData>) -> Self {
+        let bounds = Aabb::from_points(
+            parts
+                .iter()
+                .flat_map(|p| p.vertices.iter().map(|v| v.position)),
+        );
+        Self { parts, bounds }
+    }
+
+    // Returns the data along with the paths of other files it was built from (e.g. .mtl),
+    // relative to the assets dir.
+    pub async fn from_file(path: &str, src: &[u8]) -> Result<(Self, Vec<String>)> {
+        if path.ends_with(".gltf") || path.ends_with(".glb") {
+            let (doc, buffers, _) = file::read_gltf_asset(path).await?;
+            let mesh = doc
+                .meshes()
+                .next()
+                .ok_or_else(|| anyhow!("No meshes in {path}"))?;
+            // Buffer URIs are percent-encoded and relative to the .gltf
+            let deps = doc
+                .buffers()
+                .filter_map(|b| match b.source() {
+                    gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => Some(uri),
+                    _ => None,
+                })
+                .map(|uri| Ok(sibling_path(path, &urlencoding::decode(uri)?)))
+                .collect::<Result<_>>()?;
+            Ok((Self::from_gltf(&mesh, &buffers)?, deps))
+        } else {
+            Self::from_obj(path, &String::from_utf8_lossy(src)).await
+        }
+    }
+
+    // Paths in the OBJ and .mtl files are relative to the file they're in.
+    pub async fn from_obj(path: &str, data: &str) -> Result<(Self, Vec<String>)> {
+        let cursor = futures_lite::io::Cursor::new(data);
+        let mut reader = futures_lite::io::BufReader::new(cursor);
+        let mtl_paths = RefCell::new(Vec::new());
+
+        let (meshes, materials) = tobj::futures::load_obj_buf(
+            &mut reader,
+            &tobj::LoadOptions {
+                triangulate: true,
+                single_index: true,
+                ..Default::default()
+            },
+            |p| {
+                let mtl_path = sibling_path(path, p.to_str().unwrap());
+                mtl_paths.borrow_mut().push(mtl_path.clone());
+                async move {
+                    let mat_text = file::read_string_asset(&mtl_path)
+                        .await
+                        .map_err(|_| tobj::LoadError::OpenFileFailed)?;
+                    tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
+                }
+            },
+        )
+        .await?;
+        // A missing or broken .mtl shouldn't prevent the mesh from loading.
+        let materials = materials.unwrap_or_default();
+        // tobj doesn't say which .mtl a material came from, OBJs normally have just one.
+        let mtl_path = mtl_paths.borrow().first().cloned().unwrap_or_default();
+
+        let parts = meshes
+            .into_iter()
+            .map(|m| {
+                let positions = m
+                    .mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(|p| [p[0], p[1], p[2]])
+                    .collect::<Vec<_>>();
+                let uvs = (!m.mesh.texcoords.is_empty()).then(|| {
+                    m.mesh
+                        .texcoords
+                        .chunks_exact(2)
+                        .map(|t| [t[0], t[1]])
+                        .collect()
+                });
+                let normals = (!m.mesh.normals.is_empty()).then(|| {
+                    m.mesh
+                        .normals
+                        .chunks_exact(3)
+                        .map(|n| [n[0], n[1], n[2]])
+                        .collect()
+                });
+                let colors = (!m.mesh.vertex_color.is_empty()).then(|| {
+                    m.mesh
+                        .vertex_color
+                        .chunks_exact(3)
+                        .map(|c| [c[0], c[1], c[2], 1.0])
+                        .collect()
+                });
+                let vertices =
+                    geometry::build_vertices(&positions, uvs, normals, colors, &m.mesh.indices);
+
+                let material = m
+                    .mesh
+                    .material_id
+                    .and_then(|id| materials.get(id))
+                    .map(|mat| MeshMaterial {
+                        name: mat.name.clone(),
+                        diffuse_color: mat.diffuse,
+                        diffuse_texture: mat
+                            .diffuse_texture
+                            .as_ref()
+                            .map(|t| sibling_path(&mtl_path, t)),
+                    });
+
+                MeshPartData {
+                    vertices,
+                    indices: m.mesh.indices,
+                    material,
+                }
+            })
+            .collect();
+
+        Ok((Self::new(parts), mtl_paths.into_inner()))
+    }
+
+    // Each primitive of the glTF mesh becomes a separate part.
+    pub fn from_gltf(mesh: &gltf::Mesh, buffers: &[gltf::buffer::Data]) -> Result<Self> {
+        Self::from_gltf_primitives(mesh.primitives(), buffers)
+    }
+
+    // Only the triangle primitives are taken.
+    pub fn from_gltf_primitives<'a>(
+        primitives: impl IntoIterator<Item = gltf::Primitive<'a>>,
+        buffers: &[gltf::buffer::Data],
+    ) -> Result<Self> {
+        let parts = primitives
+            .into_iter()
+            .filter(|p| p.mode() == gltf::mesh::Mode::Triangles)
+            .map(|p| {
+                let reader = p.reader(|b| Some(&buffers[b.index()]));
+                let positions = reader
+                    .read_positions()
+                    .ok_or_else(|| anyhow!("glTF primitive {} without positions", p.index()))?
+                    .collect::<Vec<_>>();
+                let uvs = reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+                let normals = reader.read_normals().map(|n| n.collect());
+                let colors = reader.read_colors(0).map(|c| c.into_rgba_f32().collect());
+                let indices = reader
+                    .read_indices()
+                    .map(|i| i.into_u32().collect::<Vec<_>>())
+                    .unwrap_or_else(|| (0..positions.len() as u32).collect());
+                let vertices = geometry::build_vertices(&positions, uvs, normals, colors, &indices);
+
+                // Embedded textures can't be referenced by path, see `gltf_import` for full support.
+                let material = p.material().name().map(|name| {
+                    let [r, g, b, _] = p.material().pbr_metallic_roughness().base_color_factor();
+                    MeshMaterial {
+                        name: name.to_string(),
+                        diffuse_color: Some([r, g, b]),
+                        diffuse_texture: None,
+                    }
+                });
+
+                Ok(MeshPartData {
+                    vertices,
+                    indices,
+                    material,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self::new(parts))
+    }
+}
+
+// Path of a file referenced from another one, both relative to the assets dir.
+fn sibling_path(file: &str, path: &str) -> String {
+    match Path::new(file).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            dir.join(path).to_string_lossy().replace('\\', "/")
+        }
+        _ => path.to_string(),
+    }
+}
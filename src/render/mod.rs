@@ -1,19 +1,33 @@
+mod geometry;
+mod gpu_timer;
+mod ibl;
 mod material;
 mod mesh;
+pub mod mesh_cache;
+mod mesh_data;
+mod render_bundle;
 mod render_target;
 mod renderer;
+mod scene_bindings;
+mod shadow_pass;
 mod texture;
 mod ui;
 mod vertex;
 
 use crate::math::Mat4;
-pub use material::{Material, MaterialBuilder};
+pub use geometry::{Aabb, Frustum};
+pub use ibl::Ibl;
+pub use material::{BlendMode, Material, MaterialBuilder};
 pub use mesh::Mesh;
+pub use mesh_data::{MeshData, MeshMaterial};
+pub use render_bundle::RenderBundle;
 pub use render_target::RenderTarget;
 pub use renderer::{Renderer, SurfaceSize};
-pub use texture::Texture;
+pub use scene_bindings::SceneBindings;
+pub use shadow_pass::ShadowPass;
+pub use texture::{SamplerOptions, Texture, TextureSize};
 pub use ui::Ui;
-pub use vertex::{PositionUvNormalVertex, PositionUvVertex};
+pub use vertex::{MeshVertex, PositionColorVertex, PositionUvVertex};
 
 // Converts from "OpenGL format" to WGPU.
 #[rustfmt::skip]
@@ -0,0 +1,47 @@
+use std::ops::AddAssign;
+
+// What gets drawn, per bundle and summed up per frame, see `Renderer::end_frame`.
+#[derive(Copy, Clone, Default)]
+pub struct DrawCounts {
+    pub bundles: u32,
+    pub draw_calls: u32,
+    pub triangles: u32,
+    pub pipelines: u32,
+    pub bind_groups: u32,
+}
+
+impl AddAssign for DrawCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.bundles += other.bundles;
+        self.draw_calls += other.draw_calls;
+        self.triangles += other.triangles;
+        self.pipelines += other.pipelines;
+        self.bind_groups += other.bind_groups;
+    }
+}
+
+// A render bundle along with what it draws, which can't be asked from wgpu.
+pub struct RenderBundle {
+    bundle: wgpu::RenderBundle,
+    counts: DrawCounts,
+}
+
+impl RenderBundle {
+    pub fn new(bundle: wgpu::RenderBundle, counts: DrawCounts) -> Self {
+        Self {
+            bundle,
+            counts: DrawCounts {
+                bundles: 1,
+                ..counts
+            },
+        }
+    }
+
+    pub fn counts(&self) -> DrawCounts {
+        self.counts
+    }
+
+    pub fn inner(&self) -> &wgpu::RenderBundle {
+        &self.bundle
+    }
+}
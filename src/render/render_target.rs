@@ -2,32 +2,92 @@ use super::renderer::Renderer;
 use super::texture::Texture;
 use super::texture::TextureSize;
 
+// With MSAA rendering goes into the multisampled texture, which is then resolved into
+// the color texture.
 pub struct RenderTarget {
     color_tex: Texture,
+    msaa_tex: Option<Texture>,
     depth_tex: Texture,
+    sample_count: u32,
+    size: TextureSize,
+    clear_color: wgpu::Color,
 }
 
 impl RenderTarget {
+    // HDR target with the renderer's MSAA sample count, for rendering the scene.
     pub fn new(rr: &Renderer, size: Option<TextureSize>) -> Self {
+        Self::new_with_options(rr, size, Renderer::HDR_TEX_FORMAT, rr.sample_count())
+    }
+
+    pub fn new_with_options(
+        rr: &Renderer,
+        size: Option<TextureSize>,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
         let size = size.unwrap_or(rr.surface_size().into());
-        let color_tex = Texture::new_render_attachment(rr, rr.surface_texture_format(), size);
-        let depth_tex = Texture::new_depth(rr, rr.depth_texture_format(), size);
+        let color_tex = Texture::new_render_attachment(rr, format, size, 1);
+        let msaa_tex = (sample_count > 1)
+            .then(|| Texture::new_render_attachment(rr, format, size, sample_count));
+        let depth_tex = Texture::new_depth(rr, rr.depth_texture_format(), size, sample_count);
 
         Self {
             color_tex,
+            msaa_tex,
             depth_tex,
+            sample_count,
+            size,
+            clear_color: wgpu::Color::RED,
+        }
+    }
+
+    // Red by default, making what's not drawn over stand out.
+    pub fn with_clear_color(self, color: wgpu::Color) -> Self {
+        Self {
+            clear_color: color,
+            ..self
         }
     }
 
+    pub fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
     pub fn color_texture(&self) -> &Texture {
         &self.color_tex
     }
 
+    pub fn msaa_texture(&self) -> Option<&Texture> {
+        self.msaa_tex.as_ref()
+    }
+
     pub fn depth_texture(&self) -> &Texture {
         &self.depth_tex
     }
 
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn size(&self) -> TextureSize {
+        self.size
+    }
+
+    // The color texture must be single-sampled and 8-bit, see `Texture::read_rgba8`.
+    pub fn save_png(&self, rr: &Renderer, path: &str) -> anyhow::Result<()> {
+        self.color_tex
+            .read_rgba8(rr)?
+            .save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+
     pub fn resize(&mut self, new_size: TextureSize, rr: &Renderer) {
-        *self = RenderTarget::new(rr, Some(new_size));
+        *self = RenderTarget::new_with_options(
+            rr,
+            Some(new_size),
+            self.color_tex.format(),
+            self.sample_count,
+        )
+        .with_clear_color(self.clear_color);
     }
 }
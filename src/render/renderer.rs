@@ -1,9 +1,13 @@
+use super::gpu_timer::GpuTimer;
 use super::material::Material;
 use super::mesh::Mesh;
+use super::render_bundle::{DrawCounts, RenderBundle};
 use super::render_target::RenderTarget;
+use super::scene_bindings;
 use super::texture::Texture;
 use super::ui::Ui;
-use std::ops::Deref;
+use std::cell::{Cell, RefCell};
+use std::ops::{Deref, Range};
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
 
@@ -11,16 +15,32 @@ pub type SurfaceSize = winit::dpi::PhysicalSize<u32>;
 
 pub struct Renderer<'a> {
     pub adapter_name: String,
-    surface: wgpu::Surface<'a>,
+    // None when headless
+    surface: Option<wgpu::Surface<'a>>,
+    // Format and size of the output target when there's no surface.
     surface_cfg: wgpu::SurfaceConfiguration,
+    // Rendered into instead of the surface when set, see `set_offscreen`.
+    output: Option<RenderTarget>,
+    // Acquired before the frame, presented at its end
+    surface_tex: RefCell<Option<wgpu::SurfaceTexture>>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     depth_tex: Texture,
+    shadow_map: Texture,
+    scene_bind_group_layout: wgpu::BindGroupLayout,
+    sample_count: u32,
+    // None when the adapter can't do timestamp queries
+    gpu_timer: Option<GpuTimer>,
+    // Of the passes since the last `end_frame`
+    frame_counts: Cell<DrawCounts>,
 }
 
 impl<'a> Renderer<'a> {
     // TODO Configurable?
     pub const DEPTH_TEX_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const SHADOW_MAP_SIZE: u32 = 2048;
+    // Render targets are HDR, post-processing tonemaps them onto the surface.
+    pub const HDR_TEX_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 
     pub fn surface_texture_format(&self) -> wgpu::TextureFormat {
         self.surface_cfg.format
@@ -34,11 +54,26 @@ impl<'a> Renderer<'a> {
         SurfaceSize::new(self.surface_cfg.width, self.surface_cfg.height)
     }
 
+    // MSAA sample count of the render targets, the surface itself is always single-sampled.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
     pub fn queue(&self) -> &wgpu::Queue {
         &self.queue
     }
 
-    pub async fn new(window: Arc<winit::window::Window>) -> Renderer<'a> {
+    pub fn shadow_map(&self) -> &Texture {
+        &self.shadow_map
+    }
+
+    // Layout of group 0 of the scene materials, see `SceneBindings`.
+    pub fn scene_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.scene_bind_group_layout
+    }
+
+    // The sample count is clamped to what the adapter supports.
+    pub async fn new(window: Arc<winit::window::Window>, sample_count: u32) -> Renderer<'a> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             flags: wgpu::InstanceFlags::DEBUG,
@@ -55,21 +90,6 @@ impl<'a> Renderer<'a> {
             })
             .await
             .unwrap();
-        let adapter_name = adapter.get_info().name.clone();
-
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features {
-                    features_wgpu: wgpu::FeaturesWGPU::POLYGON_MODE_LINE,
-                    features_webgpu: wgpu::FeaturesWebGPU::empty(),
-                },
-                required_limits: wgpu::Limits::default(),
-                memory_hints: wgpu::MemoryHints::default(),
-                trace: wgpu::Trace::Off,
-            })
-            .await
-            .unwrap();
 
         let surface_size = window.inner_size();
 
@@ -94,52 +114,257 @@ impl<'a> Renderer<'a> {
                 desired_maximum_frame_latency: 2,
             }
         };
-        surface.configure(&device, &surface_cfg);
 
-        let depth_tex = Texture::new_depth(&device, Self::DEPTH_TEX_FORMAT, surface_size.into());
+        Self::with_adapter(adapter, Some(surface), surface_cfg, sample_count).await
+    }
+
+    // Renders into an offscreen output target of the given size instead of a window surface.
+    // Falls back to a software adapter (lavapipe, llvmpipe, WARP) when there's no GPU, or uses
+    // it right away with `force_fallback_adapter`, e.g. for images that don't depend on the GPU.
+    pub async fn new_headless(
+        size: SurfaceSize,
+        sample_count: u32,
+        force_fallback_adapter: bool,
+    ) -> Renderer<'a> {
+        // Software adapters may only be available via GL
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            flags: wgpu::InstanceFlags::DEBUG,
+            backend_options: wgpu::BackendOptions::default(),
+        });
+
+        let mut adapter = instance
+            .request_adapter(&headless_adapter_options(force_fallback_adapter))
+            .await;
+        if adapter.is_err() && !force_fallback_adapter {
+            adapter = instance
+                .request_adapter(&headless_adapter_options(true))
+                .await;
+        }
+
+        let surface_cfg = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        let mut rr = Self::with_adapter(
+            adapter.expect("No adapter, not even a fallback one"),
+            None,
+            surface_cfg,
+            sample_count,
+        )
+        .await;
+        rr.set_offscreen(true);
+        rr
+    }
+
+    async fn with_adapter(
+        adapter: wgpu::Adapter,
+        surface: Option<wgpu::Surface<'a>>,
+        surface_cfg: wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Renderer<'a> {
+        let adapter_name = adapter.get_info().name.clone();
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: None,
+                // BC compression is optional, textures check for it when loading.
+                // Adapter specific format features allow 2x and 8x MSAA.
+                // Software adapters may lack line polygon mode, wireframes are line lists anyway.
+                // Pass timings are only shown with timestamp queries.
+                required_features: adapter.features()
+                    & (wgpu::Features::POLYGON_MODE_LINE
+                        | wgpu::Features::TEXTURE_COMPRESSION_BC
+                        | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                        | wgpu::Features::TIMESTAMP_QUERY),
+                required_limits: wgpu::Limits::default(),
+                memory_hints: wgpu::MemoryHints::default(),
+                trace: wgpu::Trace::Off,
+            })
+            .await
+            .unwrap();
+
+        if let Some(surface) = &surface {
+            surface.configure(&device, &surface_cfg);
+        }
+
+        let sample_count = supported_sample_count(
+            &adapter,
+            &device,
+            &[Self::HDR_TEX_FORMAT, Self::DEPTH_TEX_FORMAT],
+            sample_count,
+        );
+
+        let depth_tex = Texture::new_depth(
+            &device,
+            Self::DEPTH_TEX_FORMAT,
+            (surface_cfg.width, surface_cfg.height),
+            1,
+        );
+        let shadow_map =
+            Texture::new_shadow_map(&device, Self::DEPTH_TEX_FORMAT, Self::SHADOW_MAP_SIZE);
+        let scene_bind_group_layout = scene_bindings::new_bind_group_layout(&device);
+        let gpu_timer = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| GpuTimer::new(&device, &queue));
 
         Self {
             surface_cfg,
             surface,
+            output: None,
+            surface_tex: RefCell::new(None),
             device,
             queue,
             depth_tex,
+            shadow_map,
+            scene_bind_group_layout,
+            sample_count,
             adapter_name,
+            gpu_timer,
+            frame_counts: Cell::new(DrawCounts::default()),
         }
     }
 
     pub fn resize(&mut self, new_surface_size: Option<SurfaceSize>) {
-        if let Some(SurfaceSize { width, height }) = new_surface_size {
-            if width > 0 && height > 0 {
-                self.surface_cfg.width = width;
-                self.surface_cfg.height = height;
-                self.surface.configure(&self.device, &self.surface_cfg);
-                self.depth_tex =
-                    Texture::new_depth(&self.device, Self::DEPTH_TEX_FORMAT, (width, height));
+        if let Some(SurfaceSize { width, height }) = new_surface_size
+            && width > 0
+            && height > 0
+        {
+            self.surface_cfg.width = width;
+            self.surface_cfg.height = height;
+            self.reconfigure_surface();
+            self.depth_tex =
+                Texture::new_depth(&self.device, Self::DEPTH_TEX_FORMAT, (width, height), 1);
+            if self.output.is_some() {
+                self.output = Some(RenderTarget::new_with_options(
+                    self,
+                    None,
+                    self.surface_texture_format(),
+                    1,
+                ));
             }
         }
     }
 
+    // Makes the passes without a target render into the output target, which can be read back,
+    // instead of the surface. Headless renderers are always offscreen.
+    pub fn set_offscreen(&mut self, offscreen: bool) {
+        if !offscreen {
+            self.output = None;
+        } else if self.output.is_none() {
+            self.output = Some(RenderTarget::new_with_options(
+                self,
+                None,
+                self.surface_texture_format(),
+                1,
+            ));
+        }
+    }
+
+    pub fn output(&self) -> Option<&RenderTarget> {
+        self.output.as_ref()
+    }
+
+    // Must succeed before rendering a frame into the surface, `end_frame` presents the texture.
+    // Nothing to acquire when rendering offscreen.
+    pub fn acquire_surface_texture(&self) -> Result<(), wgpu::SurfaceError> {
+        if let Some(surface) = &self.surface
+            && self.output.is_none()
+        {
+            *self.surface_tex.borrow_mut() = Some(surface.get_current_texture()?);
+        }
+        Ok(())
+    }
+
+    // E.g. after the surface got lost or outdated.
+    pub fn reconfigure_surface(&self) {
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_cfg);
+        }
+    }
+
+    // The scene bind group is set for materials that use it, see `SceneBindings`.
     pub fn build_render_bundle(
         &self,
         mesh: &Mesh,
         materials: &[&Material],
         rt: Option<&RenderTarget>,
-    ) -> wgpu::RenderBundle {
+        scene_group: Option<&wgpu::BindGroup>,
+        instances: Range<u32>,
+    ) -> RenderBundle {
         let mut encoder = self.new_bundle_encoder(rt);
+        let mut counts = DrawCounts::default();
         for part in 0..mesh.parts_count() {
-            let mat = materials.get(part.clamp(0, (materials.len() - 1).max(0) as u32) as usize);
+            let mat =
+                materials.get(part.clamp(0, materials.len().saturating_sub(1) as u32) as usize);
             if let Some(mat) = mat {
                 mat.apply(&mut encoder);
-                mesh.draw_part(part, &mut encoder);
+                if let Some(group) = scene_group
+                    && mat.uses_scene_bindings()
+                {
+                    encoder.set_bind_group(0, group, &[]);
+                    counts.bind_groups += 1;
+                }
+                mesh.draw_part(part, instances.clone(), &mut encoder);
+
+                counts.draw_calls += 1;
+                counts.pipelines += 1;
+                counts.bind_groups += mat.bind_group_count();
+                if !mat.is_wireframe() {
+                    counts.triangles += mesh.part_index_count(part) / 3 * instances.len() as u32;
+                }
+            }
+        }
+        RenderBundle::new(
+            encoder.finish(&wgpu::RenderBundleDescriptor { label: None }),
+            counts,
+        )
+    }
+
+    // Draws each range of the vertex buffer as lines with its material.
+    pub fn build_lines_bundle(
+        &self,
+        vertices: &wgpu::Buffer,
+        draws: &[(&Material, Range<u32>)],
+        rt: Option<&RenderTarget>,
+        scene_group: Option<&wgpu::BindGroup>,
+    ) -> RenderBundle {
+        let mut encoder = self.new_bundle_encoder(rt);
+        let mut counts = DrawCounts::default();
+        encoder.set_vertex_buffer(0, vertices.slice(..));
+        for (mat, range) in draws.iter().filter(|(_, range)| !range.is_empty()) {
+            mat.apply(&mut encoder);
+            if let Some(group) = scene_group
+                && mat.uses_scene_bindings()
+            {
+                encoder.set_bind_group(0, group, &[]);
+                counts.bind_groups += 1;
             }
+            encoder.draw(range.clone(), 0..1);
+
+            counts.draw_calls += 1;
+            counts.pipelines += 1;
+            counts.bind_groups += mat.bind_group_count();
         }
-        encoder.finish(&wgpu::RenderBundleDescriptor { label: None })
+        RenderBundle::new(
+            encoder.finish(&wgpu::RenderBundleDescriptor { label: None }),
+            counts,
+        )
     }
 
+    // The label names the pass in the GPU timings, the UI gets its own pass.
     pub fn render_pass(
         &self,
-        bundles: &[wgpu::RenderBundle],
+        label: &str,
+        bundles: &[&RenderBundle],
         target: Option<&RenderTarget>,
         // TODO More elegant.
         // Currently I cannot win the borrow checker and make Renderer NOT reference the Ui in some way.
@@ -147,33 +372,41 @@ impl<'a> Renderer<'a> {
         // but using the Ui in that lambda on the call site hits the lifetime wall.
         ui: Option<&mut Ui>,
     ) {
-        let surface_tex = target.is_none().then(|| {
-            self.surface
-                .get_current_texture()
-                // TODO Fix, this breaks on Linux when resizing.
-                .expect("Missing surface texture")
-        });
-        let surface_tex_view = surface_tex.as_ref().map(|t| {
-            t.texture
+        let target = target.or(self.output.as_ref());
+        let surface_tex_view = target.is_none().then(|| {
+            self.surface_tex
+                .borrow()
+                .as_ref()
+                .expect("No surface texture, see `acquire_surface_texture`")
+                .texture
                 .create_view(&wgpu::TextureViewDescriptor::default())
         });
 
+        // Multisampled targets are resolved into their color texture, the samples themselves
+        // aren't needed afterwards.
+        let msaa_tex = target.and_then(|t| t.msaa_texture());
         let color_attachment = Some(wgpu::RenderPassColorAttachment {
-            view: target
-                .map(|t| t.color_texture().view())
+            view: msaa_tex
+                .or(target.map(|t| t.color_texture()))
+                .map(|t| t.view())
                 .or(surface_tex_view.as_ref())
                 .unwrap(),
-            resolve_target: None,
+            resolve_target: msaa_tex.and(target).map(|t| t.color_texture().view()),
             ops: wgpu::Operations {
-                load: wgpu::LoadOp::Clear(wgpu::Color::RED),
-                store: wgpu::StoreOp::Store,
+                load: wgpu::LoadOp::Clear(target.map_or(wgpu::Color::RED, |t| t.clear_color())),
+                store: if msaa_tex.is_some() {
+                    wgpu::StoreOp::Discard
+                } else {
+                    wgpu::StoreOp::Store
+                },
             },
         });
 
+        let depth_view = target
+            .map(|t| t.depth_texture().view())
+            .unwrap_or(self.depth_tex.view());
         let depth_attachment = Some(wgpu::RenderPassDepthStencilAttachment {
-            view: target
-                .map(|t| t.depth_texture().view())
-                .unwrap_or(self.depth_tex.view()),
+            view: depth_view,
             depth_ops: Some(wgpu::Operations {
                 load: wgpu::LoadOp::Clear(1.0),
                 store: wgpu::StoreOp::Store,
@@ -187,27 +420,123 @@ impl<'a> Renderer<'a> {
 
             {
                 let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: None,
+                    label: Some(label),
                     color_attachments: &[color_attachment],
                     depth_stencil_attachment: depth_attachment,
                     occlusion_query_set: None,
-                    timestamp_writes: None,
+                    timestamp_writes: self.timestamp_writes(label),
                 });
 
-                pass.execute_bundles(bundles.iter());
-                if let Some(ui) = ui {
-                    ui.draw(self, &mut pass);
-                }
+                pass.execute_bundles(bundles.iter().map(|b| b.inner()));
+                self.count_draws(bundles.iter().map(|b| b.counts()));
+            }
+
+            // Over the resolved image, the UI targets are never multisampled anyway
+            if let Some(ui) = ui {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("UI"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: target
+                            .map(|t| t.color_texture().view())
+                            .or(surface_tex_view.as_ref())
+                            .unwrap(),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: depth_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    occlusion_query_set: None,
+                    timestamp_writes: self.timestamp_writes("UI"),
+                });
+                ui.draw(self, &mut pass);
             }
 
             encoder.finish()
         };
 
         self.queue.submit(Some(cmd_buf));
+    }
+
+    // Renders into the shadow map, clearing it even when there's nothing to render.
+    pub fn shadow_pass(&self, bundle: Option<&RenderBundle>) {
+        let mut encoder =
+            self.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadows"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: self.shadow_map.view(),
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: self.timestamp_writes("Shadows"),
+            });
+            pass.execute_bundles(bundle.map(|b| b.inner()));
+            self.count_draws(bundle.map(|b| b.counts()));
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    // Presents the surface texture, returns what the passes have drawn since the previous call
+    // and starts reading back their GPU timings.
+    pub fn end_frame(&self) -> DrawCounts {
+        if let Some(t) = self.surface_tex.take() {
+            t.present();
+        }
+        if let Some(timer) = &self.gpu_timer {
+            timer.end_frame(&self.device, &self.queue);
+        }
+        self.frame_counts.take()
+    }
+
+    // Milliseconds per pass label of a recent frame, none without timestamp queries.
+    pub fn gpu_timings(&self) -> Option<Vec<(String, f32)>> {
+        self.gpu_timer.as_ref().map(|t| t.timings())
+    }
+
+    fn timestamp_writes(&self, label: &str) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.gpu_timer
+            .as_ref()
+            .and_then(|t| t.timestamp_writes(label))
+    }
 
-        if let Some(t) = surface_tex {
-            t.present()
+    fn count_draws(&self, counts: impl IntoIterator<Item = DrawCounts>) {
+        let mut frame_counts = self.frame_counts.get();
+        for c in counts {
+            frame_counts += c;
         }
+        self.frame_counts.set(frame_counts);
+    }
+
+    pub fn new_shadow_bundle_encoder(&'_ self) -> wgpu::RenderBundleEncoder<'_> {
+        self.device
+            .create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                label: None,
+                multiview: None,
+                sample_count: 1,
+                color_formats: &[],
+                depth_stencil: Some(wgpu::RenderBundleDepthStencil {
+                    format: self.shadow_map.format(),
+                    depth_read_only: false,
+                    stencil_read_only: false,
+                }),
+            })
     }
 
     pub fn new_uniform_bind_group(
@@ -249,6 +578,7 @@ impl<'a> Renderer<'a> {
     pub fn new_texture_bind_group(
         &self,
         texture: &Texture,
+        sampler: &wgpu::Sampler,
         view_dimension: wgpu::TextureViewDimension,
     ) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
         let layout = self.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -282,7 +612,7 @@ impl<'a> Renderer<'a> {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(texture.sampler()),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
             ],
             label: None,
@@ -291,6 +621,81 @@ impl<'a> Renderer<'a> {
         (layout, group)
     }
 
+    // Optional uniform at binding 0, followed by a texture and sampler pair per texture.
+    pub fn new_uniform_and_textures_bind_group(
+        &self,
+        data: Option<&[u8]>,
+        textures: &[(&Texture, &wgpu::Sampler, wgpu::TextureViewDimension)],
+    ) -> (wgpu::BindGroupLayout, wgpu::BindGroup, Option<wgpu::Buffer>) {
+        let buffer = data.map(|data| {
+            self.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: data,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        });
+        let first_texture_binding = buffer.is_some() as u32;
+
+        let mut layout_entries = Vec::new();
+        let mut entries = Vec::new();
+        if let Some(buffer) = &buffer {
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            });
+        }
+        for (i, (texture, sampler, view_dimension)) in textures.iter().enumerate() {
+            let binding = first_texture_binding + 2 * i as u32;
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: *view_dimension,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: binding + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(texture.view()),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            });
+        }
+
+        let layout = self.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &layout_entries,
+            label: None,
+        });
+
+        let group = self.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &entries,
+            label: None,
+        });
+
+        (layout, group, buffer)
+    }
+
     fn new_bundle_encoder(
         &'_ self,
         target: Option<&RenderTarget>,
@@ -305,7 +710,7 @@ impl<'a> Renderer<'a> {
             .create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
                 label: None,
                 multiview: None,
-                sample_count: 1,
+                sample_count: target.map_or(1, |t| t.sample_count()),
                 color_formats: &[Some(color_format)],
                 depth_stencil: Some(wgpu::RenderBundleDepthStencil {
                     format: depth_format,
@@ -323,3 +728,46 @@ impl Deref for Renderer<'_> {
         &self.device
     }
 }
+
+fn headless_adapter_options(
+    force_fallback_adapter: bool,
+) -> wgpu::RequestAdapterOptions<'static, 'static> {
+    wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        compatible_surface: None,
+        force_fallback_adapter,
+    }
+}
+
+// The highest count not over the requested one that all the formats support. Without adapter
+// specific format features only 1 and 4 are allowed.
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    formats: &[wgpu::TextureFormat],
+    requested: u32,
+) -> u32 {
+    // Multisampled resolves come out black on the GL backend (e.g. llvmpipe).
+    if adapter.get_info().backend == wgpu::Backend::Gl {
+        return 1;
+    }
+    let adapter_specific = device
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    [8, 4, 2, 1]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| {
+            formats.iter().all(|&format| {
+                if adapter_specific {
+                    adapter
+                        .get_texture_format_features(format)
+                        .flags
+                        .sample_count_supported(count)
+                } else {
+                    count == 1 || count == 4
+                }
+            })
+        })
+        .unwrap_or(1)
+}
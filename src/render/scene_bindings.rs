@@ -0,0 +1,166 @@
+use wgpu::util::DeviceExt;
+
+use super::renderer::Renderer;
+
+// Bind group 0 of the scene materials: camera and lights uniforms, rewritten for every camera,
+// the per-object uniforms in a storage buffer uploaded once per frame, and the shadow map.
+// Shaders index the objects with the instance index, so render bundles stay valid between frames
+// as long as their instance ranges don't change.
+pub struct SceneBindings {
+    camera_buf: wgpu::Buffer,
+    lights_buf: wgpu::Buffer,
+    objects: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+    capacity: usize,
+    object_size: usize,
+    // Bumped whenever the bind group is recreated, which invalidates bundles using the old one.
+    generation: u32,
+}
+
+impl SceneBindings {
+    pub fn new(rr: &Renderer, camera_size: usize, lights_size: usize, object_size: usize) -> Self {
+        let new_uniform_buffer = |size: usize| {
+            rr.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &vec![0u8; size],
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        };
+
+        let mut res = Self {
+            camera_buf: new_uniform_buffer(camera_size),
+            lights_buf: new_uniform_buffer(lights_size),
+            objects: None,
+            capacity: 0,
+            object_size,
+            generation: 0,
+        };
+        res.reserve(rr, 1);
+        res
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.objects.as_ref().unwrap().1
+    }
+
+    pub fn update_camera(
+        &self,
+        rr: &Renderer,
+        camera: impl bytemuck::NoUninit,
+        lights: impl bytemuck::NoUninit,
+    ) {
+        rr.queue()
+            .write_buffer(&self.camera_buf, 0, bytemuck::cast_slice(&[camera]));
+        rr.queue()
+            .write_buffer(&self.lights_buf, 0, bytemuck::cast_slice(&[lights]));
+    }
+
+    pub fn update_objects<T: bytemuck::NoUninit>(&mut self, rr: &Renderer, objects: &[T]) {
+        assert_eq!(size_of::<T>(), self.object_size);
+        self.reserve(rr, objects.len());
+        rr.queue().write_buffer(
+            &self.objects.as_ref().unwrap().0,
+            0,
+            bytemuck::cast_slice(objects),
+        );
+    }
+
+    fn reserve(&mut self, rr: &Renderer, count: usize) {
+        if count <= self.capacity {
+            return;
+        }
+
+        self.capacity = count.next_power_of_two();
+        let buffer = rr.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (self.capacity * self.object_size) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let group = rr.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: rr.scene_bind_group_layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.camera_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.lights_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(rr.shadow_map().view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(rr.shadow_map().sampler()),
+                },
+            ],
+        });
+        self.objects = Some((buffer, group));
+        self.generation += 1;
+    }
+}
+
+pub fn new_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+        ],
+    })
+}
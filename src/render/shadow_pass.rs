@@ -0,0 +1,140 @@
+use crate::math::Mat4;
+
+use super::mesh::Mesh;
+use super::render_bundle::{DrawCounts, RenderBundle};
+use super::renderer::Renderer;
+use super::vertex::{MeshVertex, Vertex};
+
+const MATRIX_SIZE: usize = size_of::<[[f32; 4]; 4]>();
+
+// Renders meshes into the renderer's shadow map with a depth-only pipeline. Each mesh gets its
+// own slot in a uniform buffer, selected via a dynamic offset.
+pub struct ShadowPass {
+    pipeline: wgpu::RenderPipeline,
+    matrices: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+    capacity: usize,
+    stride: usize,
+}
+
+impl ShadowPass {
+    pub fn new(rr: &Renderer, shader: &wgpu::ShaderModule) -> Self {
+        let layout = rr.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&new_bind_group_layout(rr)],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = rr.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[MeshVertex::buffer_layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: rr.shadow_map().format(),
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // Against shadow acne
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            matrices: None,
+            capacity: 0,
+            stride: (rr.limits().min_uniform_buffer_offset_alignment as usize).max(MATRIX_SIZE),
+        }
+    }
+
+    // Casters come with their light view-projection * world matrices.
+    pub fn render(&mut self, rr: &Renderer, casters: &[(&Mesh, Mat4)]) {
+        if casters.is_empty() {
+            rr.shadow_pass(None);
+            return;
+        }
+
+        if casters.len() > self.capacity {
+            self.capacity = casters.len().next_power_of_two();
+            let buffer = rr.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: (self.capacity * self.stride) as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let group = rr.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &self.pipeline.get_bind_group_layout(0),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(MATRIX_SIZE as u64),
+                    }),
+                }],
+            });
+            self.matrices = Some((buffer, group));
+        }
+        let (buffer, group) = self.matrices.as_ref().unwrap();
+
+        let mut data = vec![0u8; casters.len() * self.stride];
+        for (i, (_, matrix)) in casters.iter().enumerate() {
+            let matrix: [[f32; 4]; 4] = (*matrix).into();
+            data[i * self.stride..i * self.stride + MATRIX_SIZE]
+                .copy_from_slice(bytemuck::cast_slice(&matrix));
+        }
+        rr.queue().write_buffer(buffer, 0, &data);
+
+        let mut encoder = rr.new_shadow_bundle_encoder();
+        let mut counts = DrawCounts {
+            pipelines: 1,
+            ..DrawCounts::default()
+        };
+        encoder.set_pipeline(&self.pipeline);
+        for (i, (mesh, _)) in casters.iter().enumerate() {
+            encoder.set_bind_group(0, group, &[(i * self.stride) as u32]);
+            counts.bind_groups += 1;
+            for part in 0..mesh.parts_count() {
+                mesh.draw_part(part, 0..1, &mut encoder);
+                counts.draw_calls += 1;
+                counts.triangles += mesh.part_index_count(part) / 3;
+            }
+        }
+        let bundle = RenderBundle::new(
+            encoder.finish(&wgpu::RenderBundleDescriptor { label: None }),
+            counts,
+        );
+
+        rr.shadow_pass(Some(&bundle));
+    }
+}
+
+fn new_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: wgpu::BufferSize::new(MATRIX_SIZE as u64),
+            },
+            count: None,
+        }],
+    })
+}
@@ -1,4 +1,7 @@
+use std::borrow::Cow;
+
 use anyhow::*;
+use half::f16;
 use image::GenericImageView;
 use wgpu::util::{DeviceExt, TextureDataOrder};
 
@@ -6,7 +9,42 @@ use super::Renderer;
 
 pub type TextureSize = (u32, u32);
 
+// How a texture is sampled. Anisotropic filtering only applies with the linear filter.
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerOptions {
+    pub filter: wgpu::FilterMode,
+    pub address_mode: wgpu::AddressMode,
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            filter: wgpu::FilterMode::Linear,
+            address_mode: wgpu::AddressMode::Repeat,
+            anisotropy: 16,
+        }
+    }
+}
+
+impl SamplerOptions {
+    pub fn new_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        let anisotropy_clamp = match self.filter {
+            wgpu::FilterMode::Linear => self.anisotropy.clamp(1, 16),
+            wgpu::FilterMode::Nearest => 1,
+        };
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            anisotropy_clamp,
+            ..new_sampler_descriptor(self.filter, self.filter, None)
+        })
+    }
+}
+
 pub struct Texture {
+    texture: wgpu::Texture,
     view: wgpu::TextureView,
     sampler: wgpu::Sampler,
     format: wgpu::TextureFormat,
@@ -19,13 +57,20 @@ impl Texture {
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
         size: TextureSize,
+        sample_count: u32,
     ) -> Self {
         let size = wgpu::Extent3d {
             width: size.0,
             height: size.1,
             depth_or_array_layers: 1,
         };
-        let texture = new_empty_texture_2d(device, size, format);
+        let texture = new_empty_texture_2d(
+            device,
+            size,
+            format,
+            sample_count,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&new_sampler_descriptor(
             wgpu::FilterMode::Nearest,
@@ -34,23 +79,61 @@ impl Texture {
         ));
 
         Self {
+            texture,
+            view,
+            sampler,
+            format,
+        }
+    }
+
+    // Depth texture sampled with a linear comparison sampler, which gives 2x2 PCF for free.
+    pub fn new_shadow_map(device: &wgpu::Device, format: wgpu::TextureFormat, size: u32) -> Self {
+        let size = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        };
+        let texture = new_empty_texture_2d(
+            device,
+            size,
+            format,
+            1,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&new_sampler_descriptor(
+            wgpu::FilterMode::Linear,
+            wgpu::FilterMode::Nearest,
+            Some(wgpu::CompareFunction::LessEqual),
+        ));
+
+        Self {
+            texture,
             view,
             sampler,
             format,
         }
     }
 
+    // Multisampled attachments can't be sampled, only resolved into single-sample ones. Those
+    // can be read back, see `read_rgba8`.
     pub fn new_render_attachment(
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
         size: TextureSize,
+        sample_count: u32,
     ) -> Self {
         let size = wgpu::Extent3d {
             width: size.0,
             height: size.1,
             depth_or_array_layers: 1,
         };
-        let texture = new_empty_texture_2d(device, size, format);
+        let mut usage =
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if sample_count == 1 {
+            usage |= wgpu::TextureUsages::COPY_SRC;
+        }
+        let texture = new_empty_texture_2d(device, size, format, sample_count, usage);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&new_sampler_descriptor(
             wgpu::FilterMode::Nearest,
@@ -59,12 +142,72 @@ impl Texture {
         ));
 
         Self {
+            texture,
             view,
             sampler,
             format,
         }
     }
 
+    // Blocks until the texture is copied back from the GPU. Only 8-bit RGBA and BGRA textures
+    // created with `COPY_SRC` can be read.
+    pub fn read_rgba8(&self, rr: &Renderer) -> Result<image::RgbaImage> {
+        let swap_red_blue = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => bail!("Reading back {format:?} textures is not supported"),
+        };
+
+        // Rows in the buffer must be aligned
+        let size = self.texture.size();
+        let row_bytes = size.width * 4;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = rr.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (padded_row_bytes * size.height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder =
+            rr.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        rr.queue().submit(Some(encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).unwrap();
+        });
+        rr.poll(wgpu::PollType::Wait)?;
+        receiver.recv()??;
+
+        let mut pixels = Vec::with_capacity((row_bytes * size.height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        for pixel in pixels.chunks_mut(4) {
+            if swap_red_blue {
+                pixel.swap(0, 2);
+            }
+            // The surface is opaque, whatever alpha ends up in the image
+            pixel[3] = 255;
+        }
+
+        image::RgbaImage::from_raw(size.width, size.height, pixels).context("Unexpected image size")
+    }
+
     pub fn view(&self) -> &wgpu::TextureView {
         &self.view
     }
@@ -77,64 +220,175 @@ impl Texture {
         self.format
     }
 
-    pub fn new_2d(rr: &Renderer, data: &[u8]) -> Result<Self> {
+    // Color textures are sRGB, data ones (normal maps and such) are not.
+    pub fn new_2d(rr: &Renderer, data: &[u8], srgb: bool) -> Result<Self> {
         let img = image::load_from_memory(data)?;
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
-        let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
+        Ok(Self::new_2d_from_rgba(
+            rr,
+            &img.to_rgba8(),
+            img.dimensions(),
+            srgb,
+        ))
+    }
+
+    // Generates the whole mip chain. Mips are downsampled in sRGB space, which is slightly off
+    // but good enough for our textures.
+    pub fn new_2d_from_rgba(rr: &Renderer, rgba: &[u8], size: TextureSize, srgb: bool) -> Self {
+        let format = if srgb {
+            Self::DEFAULT_FORMAT
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
         };
 
+        let mut mip = image::RgbaImage::from_raw(size.0, size.1, rgba.to_vec())
+            .expect("Texture data doesn't match its size");
+        let mip_level_count = size.0.max(size.1).max(1).ilog2() + 1;
+        let mut data = mip.as_raw().clone();
+        for _ in 1..mip_level_count {
+            mip = image::imageops::resize(
+                &mip,
+                (mip.width() / 2).max(1),
+                (mip.height() / 2).max(1),
+                image::imageops::FilterType::Triangle,
+            );
+            data.extend_from_slice(mip.as_raw());
+        }
+
         let texture = rr.create_texture_with_data(
             rr.queue(),
             &wgpu::TextureDescriptor {
                 label: None,
-                size,
-                mip_level_count: 1,
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: Self::DEFAULT_FORMAT,
+                format,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             },
-            TextureDataOrder::default(),
-            &rgba,
+            TextureDataOrder::LayerMajor,
+            &data,
         );
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = rr.create_sampler(&new_sampler_descriptor(
-            wgpu::FilterMode::Nearest,
-            wgpu::FilterMode::Nearest,
-            None,
-        ));
+        let sampler = SamplerOptions::default().new_sampler(rr);
 
-        Ok(Self {
+        Self {
+            texture,
             view,
             sampler,
-            format: Self::DEFAULT_FORMAT,
-        })
+            format,
+        }
     }
 
+    // Linear filtering with the given addressing, for textures created elsewhere.
+    pub fn from_view(
+        device: &wgpu::Device,
+        texture: &wgpu::Texture,
+        view: wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        address_mode: wgpu::AddressMode,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            ..new_sampler_descriptor(wgpu::FilterMode::Linear, wgpu::FilterMode::Linear, None)
+        });
+
+        Self {
+            texture: texture.clone(),
+            view,
+            sampler,
+            format,
+        }
+    }
+
+    // Size, format, mips and layers come from the DDS header. Only the first cube is used from
+    // cube arrays.
     pub fn new_cube(rr: &Renderer, data: &[u8]) -> Result<Self> {
         let image = ddsfile::Dds::read(&mut std::io::Cursor::new(&data))?;
 
-        let size = wgpu::Extent3d {
-            width: 128,
-            height: 128,
-            depth_or_array_layers: 6,
+        // 32-bit floats aren't filterable without `Features::FLOAT32_FILTERABLE`, so they're
+        // converted to 16-bit ones, as in `new_cube_from_equirect`.
+        let (format, data) = match dds_texture_format(&image)? {
+            wgpu::TextureFormat::Rgba32Float => (
+                wgpu::TextureFormat::Rgba16Float,
+                Cow::Owned(f32_to_f16(&image.data)),
+            ),
+            format => (format, Cow::Borrowed(&image.data[..])),
         };
+        if format.required_features() != wgpu::Features::empty()
+            && !rr.features().contains(format.required_features())
+        {
+            bail!("Texture format {format:?} is not supported by the adapter");
+        }
 
-        let layer_size = wgpu::Extent3d {
-            depth_or_array_layers: 1,
-            ..size
+        let is_cube = image.header.caps2.contains(ddsfile::Caps2::CUBEMAP)
+            || image
+                .header10
+                .as_ref()
+                .is_some_and(|h| h.misc_flag.contains(ddsfile::MiscFlag::TEXTURECUBE));
+        let layers = match &image.header10 {
+            Some(h) if is_cube => h.array_size * 6,
+            _ => image.get_num_array_layers(),
+        };
+        if !is_cube || layers < 6 {
+            bail!("DDS texture is not a cubemap");
+        }
+
+        let size = wgpu::Extent3d {
+            width: image.get_width(),
+            height: image.get_height(),
+            depth_or_array_layers: layers,
         };
 
         let texture = rr.create_texture_with_data(
             rr.queue(),
             &wgpu::TextureDescriptor {
                 size,
-                mip_level_count: layer_size.max_mips(wgpu::TextureDimension::D2),
+                mip_level_count: image.get_num_mipmap_levels().max(1),
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                label: None,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            &data,
+        );
+
+        Ok(Self::from_cube_texture(rr, &texture, format))
+    }
+
+    // Faces are in the +X, -X, +Y, -Y, +Z, -Z order, all of them must be square and equally sized.
+    pub fn new_cube_from_faces(rr: &Renderer, faces: &[Vec<u8>; 6]) -> Result<Self> {
+        let mut data = Vec::new();
+        let mut face_size = None;
+        for face in faces {
+            let img = image::load_from_memory(face)?;
+            let dimensions = img.dimensions();
+            if dimensions.0 != dimensions.1 || face_size.is_some_and(|s| s != dimensions) {
+                bail!("Cubemap faces must be square and of the same size");
+            }
+            face_size = Some(dimensions);
+            data.extend_from_slice(&img.to_rgba8());
+        }
+        let face_size = face_size.unwrap();
+
+        let texture = rr.create_texture_with_data(
+            rr.queue(),
+            &wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width: face_size.0,
+                    height: face_size.1,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: Self::DEFAULT_FORMAT,
@@ -142,13 +396,138 @@ impl Texture {
                 label: None,
                 view_formats: &[],
             },
+            TextureDataOrder::LayerMajor,
+            &data,
+        );
+
+        Ok(Self::from_cube_texture(rr, &texture, Self::DEFAULT_FORMAT))
+    }
+
+    // Converts an equirectangular image (.hdr, .exr or any other supported format) into a float
+    // cubemap on the GPU, using the `equirect_to_cube.wgsl` shader.
+    pub fn new_cube_from_equirect(
+        rr: &Renderer,
+        data: &[u8],
+        shader: &wgpu::ShaderModule,
+    ) -> Result<Self> {
+        const CUBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+        let img = image::load_from_memory(data)?.to_rgba32f();
+        let (width, height) = img.dimensions();
+        let max_size = rr.limits().max_texture_dimension_2d;
+        if width > max_size || height > max_size {
+            bail!(
+                "Equirectangular image is {width}x{height}, the device supports up to {max_size}"
+            );
+        }
+        let src = rr.create_texture_with_data(
+            rr.queue(),
+            &wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
             TextureDataOrder::default(),
-            &image.data,
+            bytemuck::cast_slice(img.as_raw()),
         );
+        let src_view = src.create_view(&wgpu::TextureViewDescriptor::default());
 
+        // A quarter of the width keeps roughly the same texel density.
+        let face_size = (width / 4).max(1);
+        let texture = rr.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: face_size,
+                height: face_size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CUBE_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let pipeline = rr.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(CUBE_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let bind_group = rr.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&src_view),
+            }],
+        });
+
+        let mut encoder = rr.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        for face in 0..6 {
+            let face_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: face,
+                array_layer_count: Some(1),
+                ..Default::default()
+            });
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &face_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, face..face + 1);
+        }
+        rr.queue().submit(Some(encoder.finish()));
+
+        Ok(Self::from_cube_texture(rr, &texture, CUBE_FORMAT))
+    }
+
+    fn from_cube_texture(
+        rr: &Renderer,
+        texture: &wgpu::Texture,
+        format: wgpu::TextureFormat,
+    ) -> Self {
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
             label: None,
             dimension: Some(wgpu::TextureViewDimension::Cube),
+            array_layer_count: Some(6),
             ..wgpu::TextureViewDescriptor::default()
         });
 
@@ -158,14 +537,65 @@ impl Texture {
             None,
         ));
 
-        Ok(Self {
+        Self {
+            texture: texture.clone(),
             view,
             sampler,
-            format: Self::DEFAULT_FORMAT,
-        })
+            format,
+        }
     }
 }
 
+// Formats without color space info (legacy D3D ones) are treated as sRGB.
+fn dds_texture_format(image: &ddsfile::Dds) -> Result<wgpu::TextureFormat> {
+    use ddsfile::{D3DFormat as D3d, DxgiFormat as Dxgi};
+    use wgpu::TextureFormat as Tf;
+
+    if let Some(format) = image.get_dxgi_format() {
+        return Ok(match format {
+            Dxgi::R8G8B8A8_UNorm => Tf::Rgba8Unorm,
+            Dxgi::R8G8B8A8_UNorm_sRGB => Tf::Rgba8UnormSrgb,
+            Dxgi::B8G8R8A8_UNorm => Tf::Bgra8Unorm,
+            Dxgi::B8G8R8A8_UNorm_sRGB => Tf::Bgra8UnormSrgb,
+            Dxgi::R16G16B16A16_Float => Tf::Rgba16Float,
+            Dxgi::R32G32B32A32_Float => Tf::Rgba32Float,
+            Dxgi::R11G11B10_Float => Tf::Rg11b10Ufloat,
+            Dxgi::R9G9B9E5_SharedExp => Tf::Rgb9e5Ufloat,
+            Dxgi::BC1_UNorm => Tf::Bc1RgbaUnorm,
+            Dxgi::BC1_UNorm_sRGB => Tf::Bc1RgbaUnormSrgb,
+            Dxgi::BC2_UNorm => Tf::Bc2RgbaUnorm,
+            Dxgi::BC2_UNorm_sRGB => Tf::Bc2RgbaUnormSrgb,
+            Dxgi::BC3_UNorm => Tf::Bc3RgbaUnorm,
+            Dxgi::BC3_UNorm_sRGB => Tf::Bc3RgbaUnormSrgb,
+            Dxgi::BC4_UNorm => Tf::Bc4RUnorm,
+            Dxgi::BC5_UNorm => Tf::Bc5RgUnorm,
+            Dxgi::BC6H_UF16 => Tf::Bc6hRgbUfloat,
+            Dxgi::BC6H_SF16 => Tf::Bc6hRgbFloat,
+            Dxgi::BC7_UNorm => Tf::Bc7RgbaUnorm,
+            Dxgi::BC7_UNorm_sRGB => Tf::Bc7RgbaUnormSrgb,
+            f => bail!("Unsupported DDS format {f:?}"),
+        });
+    }
+
+    match image.get_d3d_format() {
+        Some(D3d::A8B8G8R8 | D3d::X8B8G8R8) => Ok(Tf::Rgba8UnormSrgb),
+        Some(D3d::A8R8G8B8 | D3d::X8R8G8B8) => Ok(Tf::Bgra8UnormSrgb),
+        Some(D3d::A16B16G16R16F) => Ok(Tf::Rgba16Float),
+        Some(D3d::A32B32G32R32F) => Ok(Tf::Rgba32Float),
+        Some(D3d::DXT1) => Ok(Tf::Bc1RgbaUnormSrgb),
+        Some(D3d::DXT2 | D3d::DXT3) => Ok(Tf::Bc2RgbaUnormSrgb),
+        Some(D3d::DXT4 | D3d::DXT5) => Ok(Tf::Bc3RgbaUnormSrgb),
+        f => bail!("Unsupported DDS format {f:?}"),
+    }
+}
+
+// Little-endian f32s into f16s of the same endianness.
+fn f32_to_f16(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|c| f16::from_f32(f32::from_le_bytes(c.try_into().unwrap())).to_le_bytes())
+        .collect()
+}
+
 fn new_sampler_descriptor<'a>(
     filter: wgpu::FilterMode,
     mipmap_filter: wgpu::FilterMode,
@@ -191,15 +621,17 @@ fn new_empty_texture_2d(
     device: &wgpu::Device,
     size: wgpu::Extent3d,
     format: wgpu::TextureFormat,
+    sample_count: u32,
+    usage: wgpu::TextureUsages,
 ) -> wgpu::Texture {
     device.create_texture(&wgpu::TextureDescriptor {
         label: None,
         size,
         mip_level_count: 1,
-        sample_count: 1,
+        sample_count,
         dimension: wgpu::TextureDimension::D2,
         format,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        usage,
         view_formats: &[],
     })
 }
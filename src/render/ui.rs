@@ -18,19 +18,27 @@ pub struct Ui {
 impl Ui {
     const FONT_SIZE: f64 = 13.0;
 
-    pub fn new(window: &winit::window::Window, rr: &Renderer) -> Self {
+    // Without a window the UI covers the renderer's output target and gets no input.
+    pub fn new(window: Option<&winit::window::Window>, rr: &Renderer) -> Self {
         let mut context = Context::create();
 
         let mut platform = WinitPlatform::new(&mut context);
-        platform.attach_window(
-            context.io_mut(),
-            window,
-            imgui_winit_support::HiDpiMode::Default,
-        );
+        match window {
+            Some(window) => platform.attach_window(
+                context.io_mut(),
+                window,
+                imgui_winit_support::HiDpiMode::Default,
+            ),
+            None => {
+                let size = rr.surface_size();
+                context.io_mut().display_size = [size.width as f32, size.height as f32];
+            }
+        }
         context.set_ini_filename(None);
 
-        let font_size = (Self::FONT_SIZE * window.scale_factor()) as f32;
-        context.io_mut().font_global_scale = (1.0 / window.scale_factor()) as f32;
+        let scale_factor = window.map_or(1.0, |w| w.scale_factor());
+        let font_size = (Self::FONT_SIZE * scale_factor) as f32;
+        context.io_mut().font_global_scale = (1.0 / scale_factor) as f32;
 
         context.fonts().add_font(&[FontSource::DefaultFontData {
             config: Some(imgui::FontConfig {
@@ -64,21 +72,25 @@ impl Ui {
     pub fn prepare_frame(
         &mut self,
         dt: f32,
-        window: &winit::window::Window,
+        window: Option<&winit::window::Window>,
         build: impl FnOnce(&mut imgui::Ui),
     ) {
         self.context
             .io_mut()
             .update_delta_time(Duration::from_secs_f32(dt)); // TODO Avoid the conversion.
 
-        self.platform
-            .prepare_frame(self.context.io_mut(), window)
-            .expect("Failed to prepare UI frame");
+        if let Some(window) = window {
+            self.platform
+                .prepare_frame(self.context.io_mut(), window)
+                .expect("Failed to prepare UI frame");
+        }
 
         let frame = self.context.new_frame();
         build(frame);
 
-        if self.last_cursor != frame.mouse_cursor() {
+        if let Some(window) = window
+            && self.last_cursor != frame.mouse_cursor()
+        {
             self.last_cursor = frame.mouse_cursor();
             self.platform.prepare_render(frame, window);
         }
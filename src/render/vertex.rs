@@ -2,18 +2,35 @@ pub trait Vertex: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable {
     fn buffer_layout<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
 
+// Vertex format of the meshes loaded from files.
 #[repr(C)]
-#[derive(Default, Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct PositionUvNormalVertex {
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
     pub position: [f32; 3],
     pub uv: [f32; 2],
     pub normal: [f32; 3],
+    // W is the sign of the bitangent, i.e. bitangent = cross(normal, tangent.xyz) * tangent.w
+    pub tangent: [f32; 4],
+    // White when the source has no vertex colors.
+    pub color: [f32; 4],
+}
+
+impl Default for MeshVertex {
+    fn default() -> Self {
+        Self {
+            position: Default::default(),
+            uv: Default::default(),
+            normal: Default::default(),
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            color: [1.0; 4],
+        }
+    }
 }
 
-impl Vertex for PositionUvNormalVertex {
+impl Vertex for MeshVertex {
     fn buffer_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
-            array_stride: size_of::<PositionUvNormalVertex>() as wgpu::BufferAddress,
+            array_stride: size_of::<MeshVertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &[
                 wgpu::VertexAttribute {
@@ -31,6 +48,16 @@ impl Vertex for PositionUvNormalVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -63,3 +90,32 @@ impl Vertex for PositionUvVertex {
         }
     }
 }
+
+// For lines drawn without a mesh, e.g. debug shapes.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PositionColorVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl Vertex for PositionColorVertex {
+    fn buffer_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<PositionColorVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
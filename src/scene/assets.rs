@@ -1,8 +1,9 @@
 use super::materials::Material;
 use crate::file;
-use crate::render::Mesh;
+use crate::math::Vec3;
 use crate::render::Renderer;
-use crate::render::Texture;
+use crate::render::{mesh_cache, Mesh};
+use crate::render::{Ibl, Texture};
 use futures_lite::future;
 use slotmap::{DefaultKey, SlotMap};
 use std::collections::HashMap;
@@ -20,6 +21,8 @@ pub struct Assets {
     meshes: SlotMap<MeshHandle, Mesh>,
     mesh_handles: HashMap<String, MeshHandle>,
     materials: SlotMap<MaterialHandle, Material>,
+    material_handles: HashMap<String, MaterialHandle>,
+    ibl: Option<Ibl>,
 }
 
 impl Assets {
@@ -30,8 +33,10 @@ impl Assets {
             meshes: SlotMap::new(),
             mesh_handles: HashMap::new(),
             materials: SlotMap::new(),
+            material_handles: HashMap::new(),
             shaders: SlotMap::new(),
             shader_handles: HashMap::new(),
+            ibl: None,
         }
     }
 
@@ -57,48 +62,128 @@ impl Assets {
     }
 
     pub fn add_mesh(&mut self, mesh: Mesh, key: &str) -> MeshHandle {
-        self.add_mesh_impl(key, || mesh)
+        self.add_mesh_with(key, || mesh)
     }
 
     pub fn add_mesh_from_file(&mut self, rr: &Renderer, path: &str) -> MeshHandle {
-        self.add_mesh_impl(path, || {
-            let text = future::block_on(file::read_string_asset(path)).unwrap();
-            future::block_on(Mesh::from_data(rr, &text))
+        self.add_mesh_with(path, || {
+            // For glTF only the first mesh is taken, whole scenes are imported via `NodeCfg::import`.
+            let data = future::block_on(mesh_cache::load(path)).unwrap();
+            Mesh::new(rr, &data)
         })
     }
 
-    fn add_mesh_impl(&mut self, key: &str, create: impl FnOnce() -> Mesh) -> TextureHandle {
+    // Creates the mesh only if there's nothing under the key yet.
+    pub fn add_mesh_with(&mut self, key: &str, create: impl FnOnce() -> Mesh) -> MeshHandle {
         *self
             .mesh_handles
             .entry(key.to_string())
             .or_insert_with(|| self.meshes.insert(create()))
     }
 
+    // Same as `add_mesh_with`, for meshes built from data that can turn out broken.
+    pub fn try_add_mesh_with(
+        &mut self,
+        key: &str,
+        create: impl FnOnce() -> anyhow::Result<Mesh>,
+    ) -> anyhow::Result<MeshHandle> {
+        if let Some(&handle) = self.mesh_handles.get(key) {
+            return Ok(handle);
+        }
+        let handle = self.meshes.insert(create()?);
+        self.mesh_handles.insert(key.to_string(), handle);
+        Ok(handle)
+    }
+
     pub fn texture(&self, handle: TextureHandle) -> &Texture {
         self.textures.get(handle).unwrap()
     }
 
-    pub fn add_2d_texture_from_file(&mut self, rr: &Renderer, path: &str) -> TextureHandle {
-        self.add_texture(path, || {
+    // Non-sRGB textures are for data such as normal maps.
+    pub fn add_2d_texture_from_file(
+        &mut self,
+        rr: &Renderer,
+        path: &str,
+        srgb: bool,
+    ) -> TextureHandle {
+        let key = if srgb {
+            path.to_string()
+        } else {
+            format!("{path}#linear")
+        };
+        self.add_texture_with(&key, || {
             let data = future::block_on(file::read_binary_asset(path)).unwrap();
-            Texture::new_2d(rr, &data).unwrap()
+            Texture::new_2d(rr, &data, srgb).unwrap()
         })
     }
 
+    // DDS files are loaded as is, .hdr and .exr ones are treated as equirectangular images.
     pub fn add_cube_texture_from_file(&mut self, rr: &Renderer, path: &str) -> TextureHandle {
-        self.add_texture(path, || {
+        if path.ends_with(".hdr") || path.ends_with(".exr") {
+            let shader = self.add_shader_from_file(rr, "equirect_to_cube.wgsl");
+            let shader = &self.shaders[shader];
+            return *self
+                .texture_handles
+                .entry(path.to_string())
+                .or_insert_with(|| {
+                    let data = future::block_on(file::read_binary_asset(path)).unwrap();
+                    let tex = Texture::new_cube_from_equirect(rr, &data, shader)
+                        .unwrap_or_else(|e| panic!("Unable to load {path}: {e}"));
+                    self.textures.insert(tex)
+                });
+        }
+
+        self.add_texture_with(path, || {
             let data = future::block_on(file::read_binary_asset(path)).unwrap();
             Texture::new_cube(rr, &data).unwrap()
         })
     }
 
-    fn add_texture(&mut self, key: &str, create: impl FnOnce() -> Texture) -> TextureHandle {
+    // Faces in the +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn add_cube_texture_from_faces(
+        &mut self,
+        rr: &Renderer,
+        paths: &[String; 6],
+    ) -> TextureHandle {
+        self.add_texture_with(&paths.join("|"), || {
+            let faces = paths
+                .each_ref()
+                .map(|p| future::block_on(file::read_binary_asset(p)).unwrap());
+            Texture::new_cube_from_faces(rr, &faces).unwrap()
+        })
+    }
+
+    // Creates the texture only if there's nothing under the key yet.
+    pub fn add_texture_with(
+        &mut self,
+        key: &str,
+        create: impl FnOnce() -> Texture,
+    ) -> TextureHandle {
         *self
             .texture_handles
             .entry(key.to_string())
             .or_insert_with(|| self.textures.insert(create()))
     }
 
+    // Image based lighting used by the PBR materials.
+    pub fn ibl(&self) -> &Ibl {
+        self.ibl.as_ref().expect("Environment is not set")
+    }
+
+    // Lighting comes from the cubemap if there's one (anything `add_cube_texture_from_file`
+    // accepts), otherwise it's the uniform `ambient` color. Only affects materials created after.
+    pub fn set_environment(&mut self, rr: &Renderer, map: Option<&str>, ambient: Vec3) {
+        let shader = self.add_shader_from_file(rr, "ibl.wgsl");
+        let ibl = match map {
+            Some(map) => {
+                let env = self.add_cube_texture_from_file(rr, map);
+                Ibl::new(rr, &self.textures[env], &self.shaders[shader])
+            }
+            None => Ibl::new_uniform(rr, ambient, &self.shaders[shader]),
+        };
+        self.ibl = Some(ibl);
+    }
+
     pub fn material(&self, handle: MaterialHandle) -> &Material {
         &self.materials[handle]
     }
@@ -107,15 +192,59 @@ impl Assets {
         self.materials.insert(material)
     }
 
+    // Shared materials are looked up by key, entities using them can be drawn in one batch.
+    pub fn find_material(&self, key: &str) -> Option<MaterialHandle> {
+        self.material_handles.get(key).copied()
+    }
+
+    pub fn add_material_with(
+        &mut self,
+        key: &str,
+        create: impl FnOnce(&mut Self) -> Material,
+    ) -> MaterialHandle {
+        if let Some(handle) = self.find_material(key) {
+            return handle;
+        }
+        let material = create(self);
+        let handle = self.materials.insert(material);
+        self.material_handles.insert(key.to_string(), handle);
+        handle
+    }
+
     pub fn remove_material(&mut self, handle: MaterialHandle) {
         self.materials.remove(handle);
+        self.material_handles.retain(|_, &mut h| h != handle);
     }
 }
 
 async fn new_shader_module(device: &wgpu::Device, src_file_path: &str) -> wgpu::ShaderModule {
-    let src = file::read_string_asset(src_file_path).await.unwrap();
+    let src = read_shader_source(src_file_path).await;
     device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: None,
         source: wgpu::ShaderSource::Wgsl(src.into()),
     })
 }
+
+// WGSL has no includes, so `#include "file.wgsl"` lines are replaced by prepending the files,
+// which works because declarations can come in any order.
+async fn read_shader_source(path: &str) -> String {
+    let src = file::read_string_asset(path).await.unwrap();
+    let mut includes = String::new();
+    let mut rest = String::new();
+    for line in src.lines() {
+        match line
+            .strip_prefix("#include \"")
+            .and_then(|l| l.strip_suffix('"'))
+        {
+            Some(include) => {
+                includes += &file::read_string_asset(include).await.unwrap();
+                includes += "\n";
+            }
+            None => {
+                rest += line;
+                rest += "\n";
+            }
+        }
+    }
+    includes + &rest
+}
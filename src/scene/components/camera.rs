@@ -1,6 +1,8 @@
 use crate::math::{Mat4, Perspective3};
 use crate::render::RenderTarget;
 
+use super::RENDER_TAG_SCENE;
+
 pub struct Camera {
     aspect: f32,
     znear: f32,
@@ -39,7 +41,12 @@ impl Camera {
         self.target.as_mut()
     }
 
+    // Untagged scene entities are only rendered by untagged cameras, otherwise the
+    // post-processing camera would draw them too.
     pub fn should_render(&self, tags: u32) -> bool {
+        if tags == RENDER_TAG_SCENE {
+            return self.render_tags == RENDER_TAG_SCENE;
+        }
         self.render_tags & tags == tags
     }
 
@@ -0,0 +1,378 @@
+use hecs::{Entity, World};
+use std::f32::consts::TAU;
+
+use crate::input::{Input, InputAction};
+use crate::math::{Ray, UnitQuat, Vec3, Vec4};
+use crate::physics::{Isometry, Physics, SharedShape};
+use crate::scene::{DebugDraw, DebugStyle};
+
+use super::{Grab, Player, RigidBody, Transform};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+// Draggable parts of the gizmo, by the index of their axis.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Handle {
+    Axis(usize),
+    // Perpendicular to the axis
+    Plane(usize),
+    Ring(usize),
+    Scale(usize),
+    UniformScale,
+}
+
+// Where the gizmo is and how it's seen, fixed for the duration of a drag.
+#[derive(Copy, Clone)]
+struct Frame {
+    center: Vec3,
+    // Identity for translating and rotating along the world axes, the body's own for scaling
+    rotation: UnitQuat,
+    size: f32,
+    cam_forward: Vec3,
+    cam_right: Vec3,
+}
+
+impl Frame {
+    fn axis(&self, i: usize) -> Vec3 {
+        self.rotation * Vec3::ith(i, 1.0)
+    }
+
+    // Of something at the given point in the gizmo's axes.
+    fn isometry(&self, local: Vec3) -> Isometry {
+        Isometry::from_parts((self.center + self.rotation * local).into(), self.rotation)
+    }
+}
+
+struct Drag {
+    handle: Handle,
+    frame: Frame,
+    // Where the drag started on the handle's line or plane
+    start_point: Vec3,
+    start_pose: Isometry,
+    start_scale: Vec3,
+}
+
+// Editor-style gizmo on the selected body: axis and plane handles to translate it, rings to
+// rotate it and axis handles to scale it. The handles are picked with ray casts against their
+// own colliders, which aren't part of the physics world. The body is kinematic while dragged.
+pub struct Gizmo {
+    mode: GizmoMode,
+    selected: Option<Entity>,
+    hovered: Option<Handle>,
+    drag: Option<Drag>,
+}
+
+impl Gizmo {
+    // Relative to the distance from the camera, to keep the same size on the screen
+    const SIZE: f32 = 0.15;
+    const RING_SEGMENTS: usize = 32;
+    const AXIS_COLORS: [Vec4; 3] = [
+        Vec4::new(1.0, 0.2, 0.2, 1.0),
+        Vec4::new(0.2, 1.0, 0.2, 1.0),
+        Vec4::new(0.3, 0.5, 1.0, 1.0),
+    ];
+    const UNIFORM_SCALE_COLOR: Vec4 = Vec4::new(1.0, 1.0, 1.0, 1.0);
+    const ACTIVE_COLOR: Vec4 = Vec4::new(1.0, 1.0, 0.0, 1.0);
+    const MIN_SCALE: f32 = 0.05;
+
+    pub fn spawn(w: &mut World) {
+        w.spawn((Self {
+            mode: GizmoMode::Translate,
+            selected: None,
+            hovered: None,
+            drag: None,
+        },));
+    }
+
+    // Returns whether the gizmo has taken the grab button for dragging a handle.
+    pub fn update(w: &World, input: &Input, physics: &mut Physics, debug: &mut DebugDraw) -> bool {
+        let (ray, focus, cam_tr) = {
+            let mut q = w.query::<(&Player, &Transform)>();
+            let (_, (player, tr)) = q.iter().next().unwrap();
+            (player.focus_ray(), player.focus(), *tr)
+        };
+        let grabbing = w.query::<&Grab>().iter().next().is_some();
+        let mut q = w.query::<&mut Self>();
+        let (_, this) = q.iter().next().unwrap();
+
+        if this.drag.is_none() {
+            if input.action_activated(InputAction::GizmoTranslate) {
+                this.mode = GizmoMode::Translate;
+            } else if input.action_activated(InputAction::GizmoRotate) {
+                this.mode = GizmoMode::Rotate;
+            } else if input.action_activated(InputAction::GizmoScale) {
+                this.mode = GizmoMode::Scale;
+            }
+
+            // Clicking at nothing clears the selection
+            if input.action_activated(InputAction::Select) {
+                this.selected = focus.and_then(|f| {
+                    w.query::<&RigidBody>()
+                        .iter()
+                        .find(|(_, b)| b.handle() == f.body)
+                        .map(|(e, _)| e)
+                });
+            }
+        }
+
+        let Some(selected) = this.selected else {
+            return false;
+        };
+        let body = w.get::<&RigidBody>(selected).unwrap();
+        let mut tr = w.get::<&mut Transform>(selected).unwrap();
+
+        if let Some(drag) = &this.drag {
+            if !input.action_active(InputAction::Grab) {
+                body.set_kinematic(physics, false);
+                this.drag = None;
+            } else if let Some(ray) = ray
+                && let Some(point) = drag_point(drag.handle, &drag.frame, &ray)
+            {
+                let (pose, scale) = drag.apply(point);
+                physics.body_mut(body.handle()).set_position(pose, true);
+                if scale != tr.scale() {
+                    tr.set_scale(scale);
+                    body.set_scale(physics, scale);
+                }
+            }
+        }
+
+        let pose = *physics.body(body.handle()).position();
+        let frame = Frame {
+            center: pose.translation.vector,
+            rotation: if this.mode == GizmoMode::Scale {
+                pose.rotation
+            } else {
+                UnitQuat::identity()
+            },
+            size: (pose.translation.vector - cam_tr.position()).norm() * Self::SIZE,
+            cam_forward: cam_tr.forward(),
+            cam_right: cam_tr.right(),
+        };
+
+        // Grabbed bodies are released by the same button
+        this.hovered = None;
+        if this.drag.is_none()
+            && !grabbing
+            && let Some(ray) = ray
+        {
+            this.hovered = this.pick(&frame, &ray);
+            if let Some(handle) = this.hovered
+                && input.action_activated(InputAction::Grab)
+                && let Some(start_point) = drag_point(handle, &frame, &ray)
+            {
+                body.set_kinematic(physics, true);
+                this.drag = Some(Drag {
+                    handle,
+                    frame,
+                    start_point,
+                    start_pose: pose,
+                    start_scale: tr.scale(),
+                });
+            }
+        }
+
+        this.draw(&frame, debug);
+        this.drag.is_some()
+    }
+
+    fn handles(&self) -> Vec<Handle> {
+        match self.mode {
+            GizmoMode::Translate => (0..3)
+                .map(Handle::Axis)
+                .chain((0..3).map(Handle::Plane))
+                .collect(),
+            GizmoMode::Rotate => (0..3).map(Handle::Ring).collect(),
+            GizmoMode::Scale => (0..3)
+                .map(Handle::Scale)
+                .chain([Handle::UniformScale])
+                .collect(),
+        }
+    }
+
+    // The closest handle hit by the ray.
+    fn pick(&self, frame: &Frame, ray: &Ray) -> Option<Handle> {
+        self.handles()
+            .into_iter()
+            .filter_map(|handle| {
+                let (shape, pos) = collider(handle, frame);
+                shape
+                    .cast_ray(&pos, ray, f32::MAX, true)
+                    .map(|distance| (handle, distance))
+            })
+            .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+            .map(|(handle, _)| handle)
+    }
+
+    fn draw(&self, frame: &Frame, debug: &mut DebugDraw) {
+        let active = self.drag.as_ref().map(|d| d.handle).or(self.hovered);
+        let style = |handle: Handle, color: Vec4| {
+            let color = if active == Some(handle) {
+                Self::ACTIVE_COLOR
+            } else {
+                color
+            };
+            DebugStyle::new(color).on_top().always()
+        };
+        let center = frame.center;
+        let size = frame.size;
+
+        for (i, color) in Self::AXIS_COLORS.into_iter().enumerate() {
+            let axis = frame.axis(i);
+            match self.mode {
+                GizmoMode::Translate => {
+                    debug.arrow(center, center + axis * size, style(Handle::Axis(i), color));
+
+                    let (a, b) = (frame.axis((i + 1) % 3), frame.axis((i + 2) % 3));
+                    let corner = |u: f32, v: f32| center + (a * u + b * v) * size;
+                    let corners = [
+                        corner(0.25, 0.25),
+                        corner(0.45, 0.25),
+                        corner(0.45, 0.45),
+                        corner(0.25, 0.45),
+                    ];
+                    for j in 0..4 {
+                        debug.line(
+                            corners[j],
+                            corners[(j + 1) % 4],
+                            style(Handle::Plane(i), color),
+                        );
+                    }
+                }
+                GizmoMode::Rotate => {
+                    debug.circle(center, axis, size, style(Handle::Ring(i), color));
+                }
+                GizmoMode::Scale => {
+                    let style = style(Handle::Scale(i), color);
+                    debug.line(center, center + axis * size, style);
+                    debug.oriented_box(
+                        &frame.isometry(Vec3::ith(i, size)).to_homogeneous(),
+                        Vec3::from_element(size * 0.06),
+                        style,
+                    );
+                }
+            }
+        }
+
+        if self.mode == GizmoMode::Scale {
+            debug.oriented_box(
+                &frame.isometry(Vec3::zeros()).to_homogeneous(),
+                Vec3::from_element(size * 0.1),
+                style(Handle::UniformScale, Self::UNIFORM_SCALE_COLOR),
+            );
+        }
+    }
+}
+
+impl Drag {
+    // New pose and scale of the body with the handle dragged to the point.
+    fn apply(&self, point: Vec3) -> (Isometry, Vec3) {
+        let center = self.frame.center;
+        let mut pose = self.start_pose;
+        let mut scale = self.start_scale;
+        match self.handle {
+            Handle::Axis(_) | Handle::Plane(_) => {
+                pose.translation.vector += point - self.start_point;
+            }
+            Handle::Ring(i) => {
+                let axis = self.frame.axis(i);
+                let (from, to) = (self.start_point - center, point - center);
+                let angle = axis.dot(&from.cross(&to)).atan2(from.dot(&to));
+                pose.rotation = UnitQuat::from_scaled_axis(axis * angle) * pose.rotation;
+            }
+            Handle::Scale(i) => {
+                let axis = self.frame.axis(i);
+                let factor = (point - center).dot(&axis) / (self.start_point - center).dot(&axis);
+                scale[i] = (scale[i] * factor).max(Gizmo::MIN_SCALE);
+            }
+            Handle::UniformScale => {
+                let shift = (point - self.start_point).dot(&self.frame.cam_right);
+                let factor = 1.0 + shift / self.frame.size;
+                scale = scale.map(|s| (s * factor).max(Gizmo::MIN_SCALE));
+            }
+        }
+        (pose, scale)
+    }
+}
+
+// For picking, somewhat thicker than the lines of the handle.
+fn collider(handle: Handle, frame: &Frame) -> (SharedShape, Isometry) {
+    let size = frame.size;
+    let thickness = size * 0.05;
+    let cuboid =
+        |half_extents: Vec3| SharedShape::cuboid(half_extents.x, half_extents.y, half_extents.z);
+
+    match handle {
+        // From near the center to past the end of the axis
+        Handle::Axis(i) | Handle::Scale(i) => {
+            let mut half_extents = Vec3::from_element(thickness);
+            half_extents[i] = size * 0.45;
+            (
+                cuboid(half_extents),
+                frame.isometry(Vec3::ith(i, size * 0.6)),
+            )
+        }
+        Handle::Plane(i) => {
+            let mut half_extents = Vec3::from_element(size * 0.1);
+            half_extents[i] = thickness * 0.5;
+            let center = (Vec3::ith((i + 1) % 3, 1.0) + Vec3::ith((i + 2) % 3, 1.0)) * size * 0.35;
+            (cuboid(half_extents), frame.isometry(center))
+        }
+        // There's no torus shape, balls along the ring do
+        Handle::Ring(i) => {
+            let (a, b) = (Vec3::ith((i + 1) % 3, 1.0), Vec3::ith((i + 2) % 3, 1.0));
+            let balls = (0..Gizmo::RING_SEGMENTS)
+                .map(|j| {
+                    let angle = TAU * j as f32 / Gizmo::RING_SEGMENTS as f32;
+                    let pos = (a * angle.cos() + b * angle.sin()) * size;
+                    (
+                        Isometry::translation(pos.x, pos.y, pos.z),
+                        SharedShape::ball(size * 0.08),
+                    )
+                })
+                .collect();
+            (SharedShape::compound(balls), frame.isometry(Vec3::zeros()))
+        }
+        Handle::UniformScale => (
+            cuboid(Vec3::from_element(size * 0.1)),
+            frame.isometry(Vec3::zeros()),
+        ),
+    }
+}
+
+// Where the ray points at on the line or plane along which the handle moves.
+fn drag_point(handle: Handle, frame: &Frame, ray: &Ray) -> Option<Vec3> {
+    match handle {
+        Handle::Axis(i) | Handle::Scale(i) => closest_on_line(ray, frame.center, frame.axis(i)),
+        Handle::Plane(i) | Handle::Ring(i) => hit_plane(ray, frame.center, frame.axis(i)),
+        Handle::UniformScale => hit_plane(ray, frame.center, frame.cam_forward),
+    }
+}
+
+// The point of the line closest to the ray, none when they're parallel.
+fn closest_on_line(ray: &Ray, origin: Vec3, dir: Vec3) -> Option<Vec3> {
+    let w = origin - ray.origin.coords;
+    let b = dir.dot(&ray.dir);
+    let c = ray.dir.dot(&ray.dir);
+    let denom = dir.dot(&dir) * c - b * b;
+    if denom.abs() < 1e-6 {
+        return None;
+    }
+    let t = (b * ray.dir.dot(&w) - c * dir.dot(&w)) / denom;
+    Some(origin + dir * t)
+}
+
+// None when the plane is behind or along the ray.
+fn hit_plane(ray: &Ray, point: Vec3, normal: Vec3) -> Option<Vec3> {
+    let denom = normal.dot(&ray.dir);
+    if denom.abs() < 1e-6 {
+        return None;
+    }
+    let t = normal.dot(&(point - ray.origin.coords)) / denom;
+    (t >= 0.0).then(|| ray.point_at(t).coords)
+}
@@ -1,11 +1,12 @@
 use hecs::World;
 
 use crate::input::{Input, InputAction};
-use crate::math::Vec3;
+use crate::math::{Vec3, Vec4};
 use crate::physics::Physics;
+use crate::scene::{DebugDraw, DebugStyle};
 
 use super::player::Player;
-use super::RigidBody;
+use super::{RigidBody, Transform};
 
 pub struct Grab {
     // Original distance from the player when the grab was triggered.
@@ -15,7 +16,7 @@ pub struct Grab {
 }
 
 impl Grab {
-    pub fn update(world: &mut World, input: &Input, physics: &mut Physics) {
+    pub fn update(world: &mut World, input: &Input, physics: &mut Physics, debug: &mut DebugDraw) {
         fn release_grab(world: &mut World, physics: &mut Physics) {
             let entity = if let Some((entity, (_grab, body))) =
                 world.query::<(&Grab, &RigidBody)>().iter().next()
@@ -54,6 +55,14 @@ impl Grab {
                 };
                 let body = physics.body_mut(player_focus.body);
                 let offset = *body.translation() - player_focus.point;
+                // Stays for a while to be seen from elsewhere, it's a dot from the camera
+                if let Some(ray) = player_focus_ray {
+                    debug.ray(
+                        &ray,
+                        player_focus.distance,
+                        DebugStyle::new(Vec4::new(1.0, 1.0, 0.0, 1.0)).duration(3.0),
+                    );
+                }
                 world
                     .insert(
                         body_entity,
@@ -68,11 +77,20 @@ impl Grab {
 
         // Update the grabbed object if any
         if let Some(player_focus_ray) = player_focus_ray {
-            if let Some((_, (grab, body))) = world.query::<(&Grab, &RigidBody)>().iter().next() {
-                let new_pos = player_focus_ray.point_at(grab.distance) + grab.offset;
+            if let Some((_, (grab, body, tr))) = world
+                .query::<(&Grab, &RigidBody, &Transform)>()
+                .iter()
+                .next()
+            {
+                let grab_point = player_focus_ray.point_at(grab.distance);
+                let new_pos = grab_point + grab.offset;
                 physics
                     .body_mut(body.handle())
                     .set_translation(new_pos.coords, true);
+
+                let style = DebugStyle::new(Vec4::new(0.0, 1.0, 1.0, 1.0)).on_top();
+                debug.line(grab_point.coords, new_pos.coords, style);
+                debug.frame(&tr.matrix(), 1.0, style);
             }
         } else {
             // Release grab if there's no player focus anymore
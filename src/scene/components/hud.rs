@@ -1,8 +1,9 @@
 use hecs::World;
-use imgui::Condition;
+use imgui::{Condition, TableFlags};
 
 use crate::render::Ui;
-use crate::scene::components::{Player, Transform};
+use crate::scene::components::{PhysicsDebug, Player, PostProcess, Transform};
+use crate::scene::{DebugDraw, FrameStats, RenderStats};
 use crate::state::AppState;
 
 pub struct Hud;
@@ -12,15 +13,28 @@ pub struct Hud;
 // the the UI rendering code continues to be a "singleton". Maybe the UI rendering should be done
 // as a list of commands generated by various components and then fed to the Ui component.
 impl Hud {
-    pub fn update(dt: f32, w: &mut World, state: &AppState, ui: &mut Ui) {
+    pub fn update(
+        dt: f32,
+        w: &mut World,
+        state: &AppState,
+        stats: &RenderStats,
+        frame_stats: &FrameStats,
+        debug: &mut DebugDraw,
+        ui: &mut Ui,
+    ) {
         let (_, (pl_tr,)) = w
             .query_mut::<(&Transform,)>()
             .with::<&Player>()
             .into_iter()
             .next()
             .unwrap();
+        let pl_tr = *pl_tr;
+        let mut post_process = w.query::<&mut PostProcess>();
+        let (_, post_process) = post_process.iter().next().unwrap();
+        let mut physics_debug = w.query::<&mut PhysicsDebug>();
+        let (_, physics_debug) = physics_debug.iter().next().unwrap();
 
-        ui.prepare_frame(dt, &state.window, |frame| {
+        ui.prepare_frame(dt, state.window.as_deref(), |frame| {
             let window = frame.window("Info");
             window
                 .always_auto_resize(true)
@@ -32,6 +46,11 @@ impl Hud {
                     frame.text("WASDQE: move camera while mouse is captured");
                     frame.text("F: spawn a box");
                     frame.text("Left mouse click: grab/release an object");
+                    frame.text("Right mouse click: select an object for the gizmo");
+                    frame.text("1/2/3: translate/rotate/scale with the gizmo");
+                    frame.text("F1: hide the UI");
+                    frame.text("F2: show/hide debug lines");
+                    frame.text("F12: save a screenshot");
                     frame.separator();
                     frame.text(format!("Using adapter {}", state.renderer.adapter_name));
                     frame.separator();
@@ -52,6 +71,14 @@ impl Hud {
                         }
                     ));
                     frame.text(format!("Frame time: {dt:?}"));
+                    frame.text(format!(
+                        "Draw calls: {} ({} without batching)",
+                        stats.draw_calls, stats.unbatched_draw_calls
+                    ));
+                    frame.text(format!(
+                        "Objects: {} drawn, {} culled",
+                        stats.drawn, stats.culled
+                    ));
                     frame.text(format!(
                         "Player position: ({:.2}, {:.2}, {:.2})",
                         pl_tr.position().x,
@@ -64,7 +91,66 @@ impl Hud {
                         pl_tr.rotation_angles().y,
                         pl_tr.rotation_angles().z
                     ));
+                    frame.separator();
+                    let mut debug_enabled = debug.is_enabled();
+                    if frame.checkbox("Debug drawing (F2)", &mut debug_enabled) {
+                        debug.toggle();
+                    }
+                    physics_debug.build_ui(frame);
                 });
+
+            post_process.build_ui(frame);
+            Self::build_stats(frame, frame_stats);
         })
     }
+
+    fn build_stats(frame: &imgui::Ui, stats: &FrameStats) {
+        frame
+            .window("Stats")
+            .always_auto_resize(true)
+            .position([400.0, 20.0], Condition::FirstUseEver)
+            .collapsed(true, Condition::FirstUseEver)
+            .build(|| {
+                frame.text("GPU, ms");
+                if stats.gpu_supported {
+                    Self::build_stats_table(frame, "gpu", stats.gpu.rows(), 2);
+                } else {
+                    frame.text_disabled("No timestamp queries on this adapter");
+                }
+                frame.text("CPU, ms");
+                Self::build_stats_table(frame, "cpu", stats.cpu.rows(), 2);
+                frame.text("Per frame");
+                Self::build_stats_table(frame, "counts", stats.counts.rows(), 0);
+            });
+    }
+
+    // The last value, the average and the graph of each row.
+    fn build_stats_table<'a>(
+        frame: &imgui::Ui,
+        id: &str,
+        rows: impl Iterator<Item = (&'a str, &'a [f32])>,
+        precision: usize,
+    ) {
+        let Some(_table) = frame.begin_table_with_flags(id, 4, TableFlags::SIZING_FIXED_FIT) else {
+            return;
+        };
+        for (name, values) in rows {
+            let last = values.last().copied().unwrap_or_default();
+            let avg = values.iter().sum::<f32>() / values.len().max(1) as f32;
+
+            frame.table_next_row();
+            frame.table_next_column();
+            frame.text(name);
+            frame.table_next_column();
+            frame.text(format!("{last:.precision$}"));
+            frame.table_next_column();
+            frame.text_disabled(format!("avg {avg:.precision$}"));
+            frame.table_next_column();
+            frame
+                .plot_lines(format!("##{id}{name}"), values)
+                .graph_size([150.0, 20.0])
+                .scale_min(0.0)
+                .build();
+        }
+    }
 }
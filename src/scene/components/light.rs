@@ -0,0 +1,57 @@
+use crate::math::{to_point3, Mat4, Orthographic3, Vec3};
+use crate::render;
+use crate::render::Renderer;
+
+use super::Transform;
+
+pub enum LightKind {
+    Directional,
+    Point {
+        range: f32,
+    },
+    // Angles are in radians, from the light direction to the cone edge.
+    Spot {
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+// Directional and spot lights shine along the forward axis of the entity's transform.
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Light {
+    // Half the size of the area covered by the shadow map.
+    const SHADOW_EXTENT: f32 = 25.0;
+    const SHADOW_DEPTH: f32 = 100.0;
+
+    // Orthographic view-projection of a directional light covering the area around `center`.
+    // The center is snapped to the shadow map texels so the shadows don't shimmer when it moves.
+    pub fn shadow_view_proj(tr: &Transform, center: Vec3) -> Mat4 {
+        let dir = tr.forward().normalize();
+        let up = if dir.y.abs() > 0.99 {
+            Vec3::z()
+        } else {
+            Vec3::y()
+        };
+        let view = Mat4::look_at_rh(&to_point3(Vec3::zeros()), &to_point3(dir), &up);
+
+        let texel = 2.0 * Self::SHADOW_EXTENT / Renderer::SHADOW_MAP_SIZE as f32;
+        let c = view.transform_point(&to_point3(center));
+        let (x, y) = ((c.x / texel).round() * texel, (c.y / texel).round() * texel);
+        let proj = Orthographic3::new(
+            x - Self::SHADOW_EXTENT,
+            x + Self::SHADOW_EXTENT,
+            y - Self::SHADOW_EXTENT,
+            y + Self::SHADOW_EXTENT,
+            -c.z - Self::SHADOW_DEPTH / 2.0,
+            -c.z + Self::SHADOW_DEPTH / 2.0,
+        );
+
+        render::WGPU_CONVERSION_MATRIX * proj.to_homogeneous() * view
+    }
+}
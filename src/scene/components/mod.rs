@@ -1,6 +1,9 @@
 mod camera;
+mod gizmo;
 mod grab;
 mod hud;
+mod light;
+mod physics_debug;
 mod player;
 mod player_focus_marker;
 mod post_process;
@@ -8,11 +11,16 @@ mod rigid_body;
 mod transform;
 
 pub use camera::Camera;
+pub use gizmo::Gizmo;
 pub use grab::Grab;
 pub use hud::Hud;
+pub use light::{Light, LightKind};
+pub use physics_debug::PhysicsDebug;
 pub use player::Player;
 pub use player_focus_marker::PlayerFocusMarker;
-pub use post_process::PostProcess;
+pub use post_process::{
+    Outline, PostEffect, PostEffectKind, PostProcess, TonemapOperator, Tonemapping,
+};
 pub use rigid_body::{RigidBody, RigidBodyParams};
 pub use transform::Transform;
 
@@ -22,6 +30,16 @@ pub struct RenderTags(pub u32);
 pub struct RenderOrder(pub i32);
 pub struct Mesh(pub MeshHandle);
 
+// Entities without this component both cast and receive shadows.
+pub struct Shadows {
+    pub cast: bool,
+    pub receive: bool,
+}
+
+// Entities with this component are drawn even when outside of the camera frustum, e.g.
+// full-screen quads whose vertices are placed by the shader.
+pub struct NoCulling;
+
 pub struct Materials(pub [Option<MaterialHandle>; 4]);
 
 // TODO Extract to a mod
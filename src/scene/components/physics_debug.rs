@@ -0,0 +1,85 @@
+use hecs::World;
+
+use crate::math::Vec4;
+use crate::physics::Physics;
+use crate::render::Aabb;
+use crate::scene::{DebugDraw, DebugStyle};
+
+// Overlay of the actual Rapier colliders, as opposed to the meshes, colored by body type.
+// Drawn while the debug drawing is enabled.
+pub struct PhysicsDebug {
+    colliders: bool,
+    contacts: bool,
+}
+
+impl PhysicsDebug {
+    const DYNAMIC_COLOR: Vec4 = Vec4::new(0.2, 1.0, 0.2, 1.0);
+    const SLEEPING_COLOR: Vec4 = Vec4::new(0.6, 0.3, 1.0, 1.0);
+    const FIXED_COLOR: Vec4 = Vec4::new(0.6, 0.6, 0.6, 1.0);
+    const KINEMATIC_COLOR: Vec4 = Vec4::new(0.3, 0.6, 1.0, 1.0);
+    const CONTACT_COLOR: Vec4 = Vec4::new(1.0, 0.2, 0.2, 1.0);
+    const CONTACT_NORMAL_LENGTH: f32 = 0.3;
+
+    pub fn spawn(w: &mut World) {
+        w.spawn((Self {
+            colliders: true,
+            contacts: false,
+        },));
+    }
+
+    pub fn update(w: &World, physics: &Physics, debug: &mut DebugDraw) {
+        let mut q = w.query::<&Self>();
+        let (_, this) = q.iter().next().unwrap();
+
+        if this.colliders {
+            // The collider without a body is the player's, it would cut through the view
+            for collider in physics.colliders() {
+                let Some(body) = collider.parent().map(|b| physics.body(b)) else {
+                    continue;
+                };
+                let color = if body.is_kinematic() {
+                    Self::KINEMATIC_COLOR
+                } else if body.is_fixed() {
+                    Self::FIXED_COLOR
+                } else if body.is_sleeping() {
+                    Self::SLEEPING_COLOR
+                } else {
+                    Self::DYNAMIC_COLOR
+                };
+                let style = DebugStyle::new(color);
+
+                let pos = collider.position();
+                let shape = collider.shape();
+                if let Some(cuboid) = shape.as_cuboid() {
+                    debug.oriented_box(&pos.to_homogeneous(), cuboid.half_extents, style);
+                } else if let Some(ball) = shape.as_ball() {
+                    debug.sphere(pos.translation.vector, ball.radius, style);
+                } else {
+                    // Other shapes aren't used so far
+                    let aabb = collider.compute_aabb();
+                    debug.aabb(
+                        &Aabb {
+                            min: aabb.mins.coords,
+                            max: aabb.maxs.coords,
+                        },
+                        style,
+                    );
+                }
+            }
+        }
+
+        if this.contacts {
+            let style = DebugStyle::new(Self::CONTACT_COLOR).on_top();
+            for (point, normal) in physics.contacts() {
+                debug.sphere(point, 0.03, style);
+                debug.arrow(point, point + normal * Self::CONTACT_NORMAL_LENGTH, style);
+            }
+        }
+    }
+
+    pub fn build_ui(&mut self, frame: &imgui::Ui) {
+        frame.checkbox("Colliders", &mut self.colliders);
+        frame.same_line();
+        frame.checkbox("Contacts", &mut self.contacts);
+    }
+}
@@ -3,11 +3,11 @@ use std::f32::consts::PI;
 use hecs::{DynamicBundle, World};
 
 use crate::input::{Input, InputAction};
-use crate::math::{to_point3, Ray, Vec2, Vec3};
+use crate::math::{to_point3, Ray, Vec2, Vec3, Vec4};
 use crate::physics::{ColliderBuilder, ColliderHandle, Physics, RayCastResult, RigidBodyHandle};
 use crate::render::RenderTarget;
 use crate::render::Renderer;
-use crate::scene::{components, materials, Assets};
+use crate::scene::{components, materials, Assets, DebugDraw, DebugStyle};
 use crate::state::AppState;
 use crate::window::Window;
 
@@ -84,6 +84,7 @@ impl Player {
         w: &mut World,
         physics: &mut Physics,
         assets: &mut Assets,
+        debug: &mut DebugDraw,
     ) {
         let (_, (tr, cam, this)) = w
             .query_mut::<(&mut Transform, &mut Camera, &mut Player)>()
@@ -108,7 +109,9 @@ impl Player {
 
         if state.input.action_activated(InputAction::ControlPlayer) {
             this.controlled = !this.controlled;
-            state.window.set_cursor_grabbed(this.controlled);
+            if let Some(window) = &state.window {
+                window.set_cursor_grabbed(this.controlled);
+            }
         }
 
         this.update_focus(tr, cam, state, physics);
@@ -118,6 +121,26 @@ impl Player {
             let rot = tr.rotation_angles();
             w.spawn(Self::spawn_box(pos, rot, &state.renderer, physics, assets));
         }
+
+        Self::draw_focus(w, assets, debug);
+    }
+
+    // The point hit by the focus ray and the bounds of what it hit.
+    fn draw_focus(w: &World, assets: &Assets, debug: &mut DebugDraw) {
+        let mut q = w.query::<&Player>();
+        let Some(focus) = q.iter().next().unwrap().1.focus else {
+            return;
+        };
+
+        let style = DebugStyle::new(Vec4::new(1.0, 1.0, 0.0, 1.0));
+        debug.sphere(focus.point, 0.05, style.on_top());
+        if let Some((_, (tr, mesh, _))) = w
+            .query::<(&Transform, &Mesh, &RigidBody)>()
+            .iter()
+            .find(|(_, (.., body))| body.handle() == focus.body)
+        {
+            debug.aabb(&tr.world_bounds(assets.mesh(mesh.0).bounds()), style);
+        }
     }
 
     fn spawn_box(
@@ -137,12 +160,19 @@ impl Player {
             },
             physics,
         );
-        let mat = materials::Material::textured(rr, assets, "crate.png");
+        let mat = assets.add_material_with("player#box", |assets| {
+            materials::Material::textured(
+                rr,
+                assets,
+                "crate.png",
+                &materials::TexturedOptions::default(),
+            )
+        });
         let mesh = assets.mesh_handle("cube.obj"); // TODO Reuse some constant
         (
             Transform::new(pos, scale),
             Mesh(mesh),
-            Materials([Some(assets.add_material(mat)), None, None, None]),
+            Materials([Some(mat), None, None, None]),
             body,
         )
     }
@@ -212,11 +242,12 @@ impl Player {
         let ray = if self.controlled {
             // From screen center
             Some((tr.position(), tr.forward()))
-        } else if let Some(cursor_pos) = state.input.cursor_position() {
+        } else if let Some(cursor_pos) = state.input.cursor_position()
+            && let Some(window) = &state.window
+        {
             // From cursor position
-            let cursor_ndc_pos = state
-                .window
-                .normalized_coordinates(Vec2::new(cursor_pos.0, cursor_pos.1));
+            let cursor_ndc_pos =
+                window.normalized_coordinates(Vec2::new(cursor_pos.0, cursor_pos.1));
             let m = tr.matrix() * cam.proj_matrix().try_inverse().unwrap();
             let cursor_world_pos = m.transform_point(&to_point3(Vec3::new(
                 cursor_ndc_pos.x,
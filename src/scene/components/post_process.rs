@@ -1,67 +1,702 @@
 use super::{
-    super::materials::Material, super::Assets, Camera, Materials, Mesh, Player,
-    RenderOrder, RenderTags, Transform, RENDER_TAG_POST_PROCESS,
+    super::assets::{ShaderHandle, TextureHandle},
+    super::materials::{Material, PostProcessOptions},
+    super::Assets,
+    super::{MaterialHandle, MeshHandle},
+    Camera, Materials, Mesh, NoCulling, Player, RenderOrder, RenderTags, Transform,
+    RENDER_TAG_POST_PROCESS,
 };
+use crate::math::Vec4;
 use crate::render;
-use crate::render::Renderer;
+use crate::render::{RenderBundle, RenderTarget, Renderer, Texture, TextureSize};
 use hecs::World;
+use imgui::Condition;
 
-pub struct PostProcess;
+#[derive(Clone, Debug)]
+pub enum PostEffectKind {
+    Vignette {
+        radius: f32,
+        softness: f32,
+        strength: f32,
+    },
+    Gamma {
+        gamma: f32,
+    },
+    // Path to a 16x16x16 LUT image, see `post_process.wgsl` for the layout.
+    ColorGrading {
+        lut: String,
+        strength: f32,
+    },
+    Fxaa,
+    ChromaticAberration {
+        strength: f32,
+    },
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        // Of the upsampling filter, in texels of each level
+        radius: f32,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct PostEffect {
+    pub kind: PostEffectKind,
+    pub enabled: bool,
+}
+
+// Must match the shader.
+#[derive(Clone, Copy, Debug)]
+pub enum TonemapOperator {
+    None,
+    Reinhard,
+    Aces,
+    AgX,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Tonemapping {
+    pub operator: TonemapOperator,
+    // Compensation in EV, applied on top of the auto exposure if it's on.
+    pub exposure: f32,
+    pub auto_exposure: bool,
+}
+
+// Outline around the hovered and the grabbed entity, whichever are there. Their silhouettes go
+// into a mask, see `Scene::render`, and its edges are drawn over the tonemapped image. Occluded
+// parts of the entities are outlined too.
+#[derive(Clone, Copy, Debug)]
+pub struct Outline {
+    pub enabled: bool,
+    // In pixels
+    pub width: f32,
+    pub hover_color: Vec4,
+    pub grab_color: Vec4,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            width: 3.0,
+            hover_color: Vec4::new(1.0, 0.8, 0.2, 1.0),
+            grab_color: Vec4::new(0.2, 0.8, 1.0, 1.0),
+        }
+    }
+}
+
+impl Default for Tonemapping {
+    fn default() -> Self {
+        Self {
+            operator: TonemapOperator::Aces,
+            exposure: 0.0,
+            auto_exposure: false,
+        }
+    }
+}
+
+impl PostEffectKind {
+    fn name(&self) -> &'static str {
+        match self {
+            PostEffectKind::Vignette { .. } => "Vignette",
+            PostEffectKind::Gamma { .. } => "Gamma",
+            PostEffectKind::ColorGrading { .. } => "Color grading",
+            PostEffectKind::Fxaa => "FXAA",
+            PostEffectKind::ChromaticAberration { .. } => "Chromatic aberration",
+            PostEffectKind::Bloom { .. } => "Bloom",
+        }
+    }
+}
+
+// Color grading LUTs are 16x16x16, see `post_process.wgsl`.
+const LUT_SIZE: f32 = 16.0;
+const BLOOM_LEVELS: usize = 6;
+// The luminance is averaged starting from this size, then halved down to 1x1.
+const LUMINANCE_SIZE: u32 = 128;
+// Fraction of the difference to the current average luminance adapted every frame.
+const EXPOSURE_ADAPTATION_RATE: f32 = 0.05;
+const OUTLINE_MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+#[derive(Clone, Copy)]
+enum PassInput {
+    // The player camera image
+    Scene,
+    Target(usize),
+    Texture(TextureHandle),
+}
+
+// Where the params of a pass come from, so that they can be written again when the settings
+// change, without rebuilding the passes.
+#[derive(Clone, Copy)]
+enum Params {
+    Fixed([f32; 4]),
+    // By the index of the effect
+    Effect(usize),
+    BloomPrefilter(usize),
+    BloomUpsample(usize),
+    Tonemap,
+    Outline,
+}
+
+struct Pass {
+    entry_point: &'static str,
+    params: Params,
+    src: PassInput,
+    aux: Option<PassInput>,
+}
+
+// A pass before the final one, with what's needed to update its params.
+struct BuiltPass {
+    target: usize,
+    bundle: RenderBundle,
+    material: Material,
+    params: Params,
+    src_size: TextureSize,
+}
+
+// Applies the effects in order to the HDR image of the player camera, then tonemaps it onto
+// the surface. Effects render into two ping-pong targets, each one reading from the other,
+// and some of them (bloom, auto exposure) have extra passes with their own targets. The final
+// pass is drawn by this entity's camera. The passes are only rebuilt when they change, while
+// their params are written in place.
+pub struct PostProcess {
+    effects: Vec<PostEffect>,
+    tonemapping: Tonemapping,
+    outline: Outline,
+    // The outline mask target and the hover and grab materials for drawing into it
+    outline_mask: Option<(usize, [MaterialHandle; 2])>,
+    shader: ShaderHandle,
+    quad: MeshHandle,
+    targets: Vec<RenderTarget>,
+    passes: Vec<BuiltPass>,
+    // Params and source size of the final pass, whose material is this entity's
+    final_pass: (Params, TextureSize),
+    src_size: TextureSize,
+    // The passes need rebuilding
+    dirty: bool,
+    // Only the params of the passes need updating
+    params_dirty: bool,
+}
 
 impl PostProcess {
     pub fn spawn(w: &mut World, rr: &Renderer, assets: &mut Assets) {
+        let shader = assets.add_shader_from_file(rr, "post_process.wgsl");
+        let quad = assets.add_mesh(render::Mesh::new_quad(rr), "quad");
+
+        let mut pp = Self {
+            effects: vec![PostEffect {
+                kind: PostEffectKind::Vignette {
+                    radius: 1.0,
+                    softness: 1.0,
+                    strength: 1.0,
+                },
+                enabled: true,
+            }],
+            tonemapping: Tonemapping::default(),
+            outline: Outline::default(),
+            outline_mask: None,
+            shader,
+            quad,
+            targets: Vec::new(),
+            passes: Vec::new(),
+            final_pass: (Params::Tonemap, (0, 0)),
+            src_size: (0, 0),
+            dirty: false,
+            params_dirty: false,
+        };
         let mat = {
-            let src_tex = Self::player_camera_mut(w)
+            let mut player_cam = w.query::<&Camera>().with::<&Player>();
+            let scene = player_cam
+                .iter()
+                .next()
+                .unwrap()
+                .1
                 .target()
                 .as_ref()
-                .unwrap()
-                .color_texture();
-            Material::post_process(rr, assets, src_tex)
+                .unwrap();
+            pp.rebuild(rr, assets, scene)
         };
-        let mesh = assets.add_mesh(render::Mesh::new_quad(rr), "quad");
 
         w.spawn((
-            PostProcess,
+            pp,
             Transform::default(),
             Camera::new(1.0, RENDER_TAG_POST_PROCESS, None),
-            Mesh(mesh),
+            Mesh(quad),
             Materials::single(assets.add_material(mat)),
             RenderOrder(100),
             RenderTags(RENDER_TAG_POST_PROCESS),
+            NoCulling,
         ));
     }
 
-    pub fn update(w: &mut World, rr: &Renderer, assets: &mut Assets) {
-        let new_mat = {
-            let player_cam = Self::player_camera(w);
-            let tex = player_cam.target().as_ref().unwrap().color_texture();
-            Material::post_process(rr, assets, tex)
-        };
-        let (_, mats) = w
-            .query_mut::<&mut Materials>()
-            .with::<&Self>()
-            .into_iter()
-            .next()
-            .unwrap();
-        assets.remove_material(mats.0.get_mut(0).unwrap().unwrap());
-        mats.0[0] = Some(assets.add_material(new_mat));
+    pub fn set_effects(w: &mut World, effects: Vec<PostEffect>) {
+        let (_, pp) = w.query_mut::<&mut Self>().into_iter().next().unwrap();
+        pp.effects = effects;
+        pp.dirty = true;
     }
 
-    fn player_camera_mut(w: &mut World) -> &mut Camera {
-        w.query_mut::<&mut Camera>()
-            .with::<&Player>()
-            .into_iter()
+    pub fn set_tonemapping(w: &mut World, tonemapping: Tonemapping) {
+        let (_, pp) = w.query_mut::<&mut Self>().into_iter().next().unwrap();
+        pp.tonemapping = tonemapping;
+        pp.dirty = true;
+    }
+
+    pub fn set_outline(w: &mut World, outline: Outline) {
+        let (_, pp) = w.query_mut::<&mut Self>().into_iter().next().unwrap();
+        pp.outline = outline;
+        pp.dirty = true;
+    }
+
+    // The target to draw the silhouettes of the outlined entities into, with the hover and the
+    // grab materials to draw them with. None when the outline is disabled.
+    pub fn outline_mask(&self) -> Option<(&RenderTarget, [MaterialHandle; 2])> {
+        self.outline_mask
+            .map(|(target, materials)| (&self.targets[target], materials))
+    }
+
+    // Rebuilds the passes when the effects, whether auto exposure or the outline are on, or the
+    // size of the player camera image change. Other settings only update the params.
+    pub fn update(w: &mut World, rr: &Renderer, assets: &mut Assets) {
+        let mut player_cam = w.query::<&Camera>().with::<&Player>();
+        let scene = player_cam
+            .iter()
             .next()
             .unwrap()
             .1
+            .target()
+            .as_ref()
+            .unwrap();
+        let mut pp = w.query::<(&mut Self, &mut Materials)>();
+        let (_, (pp, mats)) = pp.iter().next().unwrap();
+
+        if !pp.dirty && scene.size() == pp.src_size {
+            if pp.params_dirty {
+                pp.write_params(rr, assets, mats.0[0].unwrap());
+            }
+            return;
+        }
+
+        let mat = pp.rebuild(rr, assets, scene);
+        assets.remove_material(mats.0[0].unwrap());
+        mats.0[0] = Some(assets.add_material(mat));
+    }
+
+    // Must run after the player camera and before this entity's camera.
+    pub fn render_passes(&self, rr: &Renderer) {
+        for pass in &self.passes {
+            rr.render_pass(
+                "Post-processing",
+                &[&pass.bundle],
+                Some(&self.targets[pass.target]),
+                None,
+            );
+        }
+    }
+
+    fn write_params(&mut self, rr: &Renderer, assets: &Assets, final_material: MaterialHandle) {
+        self.params_dirty = false;
+        for pass in &self.passes {
+            pass.material
+                .set_post_process_params(rr, self.params(pass.params), pass.src_size);
+        }
+        let (params, src_size) = self.final_pass;
+        assets
+            .material(final_material)
+            .set_post_process_params(rr, self.params(params), src_size);
+        if let Some((_, materials)) = self.outline_mask {
+            for (mat, color) in materials
+                .into_iter()
+                .zip([self.outline.hover_color, self.outline.grab_color])
+            {
+                assets.material(mat).set_silhouette_color(rr, color);
+            }
+        }
+    }
+
+    fn params(&self, params: Params) -> [f32; 4] {
+        let bloom = |i: usize| match self.effects[i].kind {
+            PostEffectKind::Bloom {
+                threshold, radius, ..
+            } => (threshold, radius),
+            _ => unreachable!(),
+        };
+        match params {
+            Params::Fixed(params) => params,
+            Params::Effect(i) => match self.effects[i].kind {
+                PostEffectKind::Vignette {
+                    radius,
+                    softness,
+                    strength,
+                } => [radius, softness, strength, 0.0],
+                PostEffectKind::Gamma { gamma } => [gamma, 0.0, 0.0, 0.0],
+                PostEffectKind::ColorGrading { strength, .. } => [strength, LUT_SIZE, 0.0, 0.0],
+                PostEffectKind::Fxaa => [0.0; 4],
+                PostEffectKind::ChromaticAberration { strength } => [strength, 0.0, 0.0, 0.0],
+                PostEffectKind::Bloom { intensity, .. } => [intensity, 0.0, 0.0, 0.0],
+            },
+            Params::BloomPrefilter(i) => [bloom(i).0, 0.0, 0.0, 0.0],
+            Params::BloomUpsample(i) => [bloom(i).1, 0.0, 0.0, 0.0],
+            Params::Tonemap => [
+                self.tonemapping.exposure,
+                self.tonemapping.operator as u32 as f32,
+                self.tonemapping.auto_exposure as u32 as f32,
+                0.0,
+            ],
+            Params::Outline => [self.outline.width, 0.0, 0.0, 0.0],
+        }
     }
 
-    fn player_camera(w: &mut World) -> &Camera {
-        w.query_mut::<&Camera>()
-            .with::<&Player>()
+    // Returns the material of the final pass.
+    fn rebuild(&mut self, rr: &Renderer, assets: &mut Assets, scene: &RenderTarget) -> Material {
+        self.dirty = false;
+        self.params_dirty = false;
+        self.src_size = scene.size();
+        self.targets.clear();
+        self.passes.clear();
+        for mat in self
+            .outline_mask
+            .take()
             .into_iter()
-            .next()
-            .unwrap()
-            .1
+            .flat_map(|(_, mats)| mats)
+        {
+            assets.remove_material(mat);
+        }
+
+        let ping_pong = [
+            self.add_target(rr, scene.size()),
+            self.add_target(rr, scene.size()),
+        ];
+        let effects = self
+            .effects
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.enabled)
+            .map(|(i, e)| (i, e.kind.clone()))
+            .collect::<Vec<_>>();
+        let effects_count = effects.len();
+        let mut input = PassInput::Scene;
+        for (n, (i, effect)) in effects.into_iter().enumerate() {
+            let (entry_point, aux) = match effect {
+                PostEffectKind::Vignette { .. } => ("fs_vignette", None),
+                PostEffectKind::Gamma { .. } => ("fs_gamma", None),
+                PostEffectKind::ColorGrading { lut, .. } => (
+                    "fs_color_grading",
+                    Some(PassInput::Texture(
+                        assets.add_2d_texture_from_file(rr, &lut, false),
+                    )),
+                ),
+                PostEffectKind::Fxaa => ("fs_fxaa", None),
+                PostEffectKind::ChromaticAberration { .. } => ("fs_chromatic_aberration", None),
+                PostEffectKind::Bloom { .. } => (
+                    "fs_bloom_composite",
+                    Some(self.add_bloom_passes(rr, assets, scene, input, i)),
+                ),
+            };
+            let pass = Pass {
+                entry_point,
+                params: Params::Effect(i),
+                src: input,
+                aux,
+            };
+            let target = ping_pong[n % 2];
+            self.add_pass(rr, assets, scene, &pass, target);
+            input = PassInput::Target(target);
+        }
+
+        let luminance = self
+            .tonemapping
+            .auto_exposure
+            .then(|| self.add_exposure_passes(rr, assets, scene));
+        let tonemap = Pass {
+            entry_point: "fs_tonemap",
+            params: Params::Tonemap,
+            src: input,
+            aux: luminance,
+        };
+        if !self.outline.enabled {
+            return self.final_pass_material(rr, assets, scene, &tonemap);
+        }
+
+        // Into the ping-pong target the last effect didn't render into
+        let tonemapped = ping_pong[effects_count % 2];
+        self.add_pass(rr, assets, scene, &tonemap, tonemapped);
+        let mask = self.targets.len();
+        self.targets.push(
+            RenderTarget::new_with_options(rr, Some(scene.size()), OUTLINE_MASK_FORMAT, 1)
+                .with_clear_color(wgpu::Color::TRANSPARENT),
+        );
+        let materials = [self.outline.hover_color, self.outline.grab_color].map(|color| {
+            let mat = Material::silhouette(rr, assets, color, OUTLINE_MASK_FORMAT);
+            assets.add_material(mat)
+        });
+        self.outline_mask = Some((mask, materials));
+
+        let outline = Pass {
+            entry_point: "fs_outline",
+            params: Params::Outline,
+            src: PassInput::Target(tonemapped),
+            aux: Some(PassInput::Target(mask)),
+        };
+        self.final_pass_material(rr, assets, scene, &outline)
+    }
+
+    fn final_pass_material(
+        &mut self,
+        rr: &Renderer,
+        assets: &Assets,
+        scene: &RenderTarget,
+        pass: &Pass,
+    ) -> Material {
+        self.final_pass = (pass.params, self.input(pass.src, scene, assets).1);
+        self.pass_material(rr, assets, scene, pass, rr.surface_texture_format())
+    }
+
+    // Returns the input with the bloom to add to the source.
+    fn add_bloom_passes(
+        &mut self,
+        rr: &Renderer,
+        assets: &Assets,
+        scene: &RenderTarget,
+        src: PassInput,
+        effect: usize,
+    ) -> PassInput {
+        let mut size = self.input(src, scene, assets).1;
+        let mut input = src;
+        let mut levels = Vec::new();
+        for level in 0..BLOOM_LEVELS {
+            size = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+            let target = self.add_target(rr, size);
+            let pass = Pass {
+                entry_point: if level == 0 {
+                    "fs_bloom_prefilter"
+                } else {
+                    "fs_bloom_downsample"
+                },
+                params: if level == 0 {
+                    Params::BloomPrefilter(effect)
+                } else {
+                    Params::Fixed([0.0; 4])
+                },
+                src: input,
+                aux: None,
+            };
+            self.add_pass(rr, assets, scene, &pass, target);
+            levels.push(target);
+            input = PassInput::Target(target);
+        }
+
+        for &level in levels.iter().rev().skip(1) {
+            let target = self.add_target(rr, self.targets[level].size());
+            let pass = Pass {
+                entry_point: "fs_bloom_upsample",
+                params: Params::BloomUpsample(effect),
+                src: input,
+                aux: Some(PassInput::Target(level)),
+            };
+            self.add_pass(rr, assets, scene, &pass, target);
+            input = PassInput::Target(target);
+        }
+
+        input
+    }
+
+    // Returns the input with the adapted log luminance of the scene.
+    fn add_exposure_passes(
+        &mut self,
+        rr: &Renderer,
+        assets: &Assets,
+        scene: &RenderTarget,
+    ) -> PassInput {
+        let mut size = LUMINANCE_SIZE;
+        let mut target = self.add_target(rr, (size, size));
+        let pass = Pass {
+            entry_point: "fs_log_luminance",
+            params: Params::Fixed([0.0; 4]),
+            src: PassInput::Scene,
+            aux: None,
+        };
+        self.add_pass(rr, assets, scene, &pass, target);
+        while size > 1 {
+            size /= 2;
+            let src = PassInput::Target(target);
+            target = self.add_target(rr, (size, size));
+            let pass = Pass {
+                entry_point: "fs_copy",
+                params: Params::Fixed([0.0; 4]),
+                src,
+                aux: None,
+            };
+            self.add_pass(rr, assets, scene, &pass, target);
+        }
+
+        // The adapted value is copied for reading in the next frame
+        let adapted = self.add_target(rr, (1, 1));
+        let previous = self.add_target(rr, (1, 1));
+        let adapt = Pass {
+            entry_point: "fs_adapt_luminance",
+            params: Params::Fixed([EXPOSURE_ADAPTATION_RATE, 0.0, 0.0, 0.0]),
+            src: PassInput::Target(target),
+            aux: Some(PassInput::Target(previous)),
+        };
+        self.add_pass(rr, assets, scene, &adapt, adapted);
+        let copy = Pass {
+            entry_point: "fs_copy",
+            params: Params::Fixed([0.0; 4]),
+            src: PassInput::Target(adapted),
+            aux: None,
+        };
+        self.add_pass(rr, assets, scene, &copy, previous);
+
+        PassInput::Target(adapted)
+    }
+
+    fn add_target(&mut self, rr: &Renderer, size: TextureSize) -> usize {
+        self.targets.push(RenderTarget::new_with_options(
+            rr,
+            Some(size),
+            Renderer::HDR_TEX_FORMAT,
+            1,
+        ));
+        self.targets.len() - 1
+    }
+
+    fn add_pass(
+        &mut self,
+        rr: &Renderer,
+        assets: &Assets,
+        scene: &RenderTarget,
+        pass: &Pass,
+        target: usize,
+    ) {
+        let format = self.targets[target].color_texture().format();
+        let material = self.pass_material(rr, assets, scene, pass, format);
+        let bundle = rr.build_render_bundle(
+            assets.mesh(self.quad),
+            &[material.inner()],
+            Some(&self.targets[target]),
+            None,
+            0..1,
+        );
+        self.passes.push(BuiltPass {
+            target,
+            bundle,
+            material,
+            params: pass.params,
+            src_size: self.input(pass.src, scene, assets).1,
+        });
+    }
+
+    fn pass_material(
+        &self,
+        rr: &Renderer,
+        assets: &Assets,
+        scene: &RenderTarget,
+        pass: &Pass,
+        color_format: wgpu::TextureFormat,
+    ) -> Material {
+        let (src, src_size) = self.input(pass.src, scene, assets);
+        let options = PostProcessOptions {
+            entry_point: pass.entry_point,
+            params: self.params(pass.params),
+            src,
+            src_size,
+            aux: pass.aux.map(|aux| self.input(aux, scene, assets).0),
+            color_format,
+        };
+        Material::post_process(rr, assets.shader(self.shader), &options)
+    }
+
+    // Size isn't known for asset textures, they can only be used as the aux input.
+    fn input<'a>(
+        &'a self,
+        input: PassInput,
+        scene: &'a RenderTarget,
+        assets: &'a Assets,
+    ) -> (&'a Texture, TextureSize) {
+        match input {
+            PassInput::Scene => (scene.color_texture(), scene.size()),
+            PassInput::Target(idx) => (self.targets[idx].color_texture(), self.targets[idx].size()),
+            PassInput::Texture(handle) => (assets.texture(handle), (1, 1)),
+        }
+    }
+
+    pub fn build_ui(&mut self, frame: &imgui::Ui) {
+        frame
+            .window("Post-processing")
+            .always_auto_resize(true)
+            .position([20.0, 400.0], Condition::FirstUseEver)
+            .build(|| {
+                let tonemapping = &mut self.tonemapping;
+                let mut operator = tonemapping.operator as usize;
+                if frame.combo_simple_string(
+                    "Tonemapping",
+                    &mut operator,
+                    &["None", "Reinhard", "ACES", "AgX"],
+                ) {
+                    tonemapping.operator = [
+                        TonemapOperator::None,
+                        TonemapOperator::Reinhard,
+                        TonemapOperator::Aces,
+                        TonemapOperator::AgX,
+                    ][operator];
+                    self.params_dirty = true;
+                }
+                self.dirty |= frame.checkbox("Auto exposure", &mut tonemapping.auto_exposure);
+                self.params_dirty |=
+                    frame.slider("Exposure, EV", -5.0, 5.0, &mut tonemapping.exposure);
+                frame.separator();
+
+                let outline = &mut self.outline;
+                self.dirty |= frame.checkbox("Outline", &mut outline.enabled);
+                self.params_dirty |= frame.slider("Width", 1.0, 8.0, &mut outline.width);
+                for (label, color) in [
+                    ("Hover color", &mut outline.hover_color),
+                    ("Grab color", &mut outline.grab_color),
+                ] {
+                    let mut rgba: [f32; 4] = (*color).into();
+                    if frame.color_edit4(label, &mut rgba) {
+                        *color = rgba.into();
+                        self.params_dirty = true;
+                    }
+                }
+                frame.separator();
+
+                for (i, effect) in self.effects.iter_mut().enumerate() {
+                    let _id = frame.push_id_usize(i);
+                    self.dirty |= frame.checkbox(effect.kind.name(), &mut effect.enabled);
+                    let mut slider = |label: &str, min: f32, max: f32, value: &mut f32| {
+                        self.params_dirty |= frame.slider(label, min, max, value);
+                    };
+                    match &mut effect.kind {
+                        PostEffectKind::Vignette {
+                            radius,
+                            softness,
+                            strength,
+                        } => {
+                            slider("Radius", 0.0, 2.0, radius);
+                            slider("Softness", 0.0, 2.0, softness);
+                            slider("Strength", 0.0, 1.0, strength);
+                        }
+                        PostEffectKind::Gamma { gamma } => slider("Gamma", 0.2, 4.0, gamma),
+                        PostEffectKind::ColorGrading { strength, .. } => {
+                            slider("Strength", 0.0, 1.0, strength);
+                        }
+                        PostEffectKind::Fxaa => (),
+                        PostEffectKind::ChromaticAberration { strength } => {
+                            slider("Strength", 0.0, 0.05, strength);
+                        }
+                        PostEffectKind::Bloom {
+                            threshold,
+                            intensity,
+                            radius,
+                        } => {
+                            slider("Threshold", 0.0, 4.0, threshold);
+                            slider("Intensity", 0.0, 2.0, intensity);
+                            slider("Radius", 0.5, 4.0, radius);
+                        }
+                    }
+                    frame.separator();
+                }
+            });
     }
 }
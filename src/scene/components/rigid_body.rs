@@ -1,5 +1,7 @@
 use crate::math::Vec3;
-use crate::physics::{ColliderBuilder, Physics, RigidBodyBuilder, RigidBodyHandle, RigidBodyType};
+use crate::physics::{
+    ColliderBuilder, Physics, RigidBodyBuilder, RigidBodyHandle, RigidBodyType, SharedShape,
+};
 
 pub struct RigidBodyParams {
     pub pos: Vec3,
@@ -14,6 +16,7 @@ pub struct RigidBody {
 }
 
 impl RigidBody {
+    // Half-extents are the scale, matching `cube.obj` which spans -1..1.
     pub fn cuboid(params: RigidBodyParams, physics: &mut Physics) -> Self {
         let RigidBodyParams {
             pos,
@@ -49,6 +52,16 @@ impl RigidBody {
         };
         body.set_body_type(new_type, true);
     }
+
+    // Resizes the colliders along with the mesh, they're all cuboids.
+    pub fn set_scale(&self, physics: &mut Physics, scale: Vec3) {
+        let colliders = physics.body(self.handle).colliders().to_vec();
+        for collider in colliders {
+            physics
+                .collider_mut(collider)
+                .set_shape(SharedShape::cuboid(scale.x, scale.y, scale.z));
+        }
+    }
 }
 
 fn body_type(movable: bool) -> RigidBodyType {
@@ -1,4 +1,5 @@
-use crate::math::{Mat4, Quat, Rotation3, Translation3, UnitQuat, Vec3};
+use crate::math::{Mat3, Mat4, Quat, Rotation3, Translation3, UnitQuat, Vec3};
+use crate::render::Aabb;
 
 pub enum TransformSpace {
     Local,
@@ -27,10 +28,48 @@ impl Transform {
         Transform::new(pos, Vec3::from_element(1.0))
     }
 
+    // Decomposes a TRS matrix, shear is lost. A negative determinant is put into the x scale,
+    // and a zero scale leaves the rotation undefined, so it's taken as identity.
+    pub fn from_matrix(m: &Mat4) -> Self {
+        let pos = m.column(3).xyz();
+        let mut scale = Vec3::new(
+            m.column(0).xyz().magnitude(),
+            m.column(1).xyz().magnitude(),
+            m.column(2).xyz().magnitude(),
+        );
+        if m.fixed_view::<3, 3>(0, 0).determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+        let rot = if scale.iter().any(|s| s.abs() <= f32::EPSILON) {
+            UnitQuat::identity()
+        } else {
+            let rot_m = Mat3::from_columns(&[
+                m.column(0).xyz() / scale.x,
+                m.column(1).xyz() / scale.y,
+                m.column(2).xyz() / scale.z,
+            ]);
+            // Stored inverted, see `rebuild_matrix`.
+            UnitQuat::from_matrix(&rot_m).inverse()
+        };
+        let mut res = Self {
+            m: *m,
+            rot,
+            scale,
+            pos,
+        };
+        res.rebuild_matrix();
+        res
+    }
+
     pub fn matrix(&self) -> Mat4 {
         self.m
     }
 
+    // World space bounds of something with the given local bounds.
+    pub fn world_bounds(&self, local: &Aabb) -> Aabb {
+        local.transformed(&self.m)
+    }
+
     pub fn view_matrix(&self) -> Mat4 {
         self.m.try_inverse().unwrap()
     }
@@ -53,12 +92,23 @@ impl Transform {
         self.pos
     }
 
+    pub fn scale(&self) -> Vec3 {
+        self.scale
+    }
+
     pub fn rotation_angles(&self) -> Vec3 {
         self.rot.scaled_axis()
     }
 
     pub fn look_at(&mut self, target: Vec3) {
-        self.rot = UnitQuat::look_at_rh(&(target - self.pos), &Vec3::y_axis());
+        let dir = target - self.pos;
+        // The up vector can't be parallel to the direction, e.g. for lights pointing down
+        let up = if dir.normalize().y.abs() > 0.99 {
+            Vec3::z_axis()
+        } else {
+            Vec3::y_axis()
+        };
+        self.rot = UnitQuat::look_at_rh(&dir, &up);
         self.rebuild_matrix();
     }
 
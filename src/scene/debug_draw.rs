@@ -0,0 +1,285 @@
+use std::f32::consts::TAU;
+use std::ops::Range;
+
+use crate::math::{Mat4, Ray, Vec3, Vec4};
+use crate::render::{Aabb, PositionColorVertex, RenderBundle, RenderTarget, Renderer};
+
+use super::assets::{Assets, MaterialHandle};
+use super::materials;
+
+// How a debug shape is drawn, e.g. `DebugStyle::new(color).duration(2.0).on_top()`.
+#[derive(Copy, Clone, Debug)]
+pub struct DebugStyle {
+    color: Vec4,
+    // Seconds, zero for just the current frame
+    duration: f32,
+    depth_test: bool,
+    always: bool,
+}
+
+impl DebugStyle {
+    // For one frame, hidden by the geometry in front of it.
+    pub fn new(color: Vec4) -> Self {
+        Self {
+            color,
+            duration: 0.0,
+            depth_test: true,
+            always: false,
+        }
+    }
+
+    pub fn duration(self, secs: f32) -> Self {
+        Self {
+            duration: secs,
+            ..self
+        }
+    }
+
+    // Drawn over everything, without the depth test.
+    pub fn on_top(self) -> Self {
+        Self {
+            depth_test: false,
+            ..self
+        }
+    }
+
+    // Drawn even while the debug drawing is disabled, e.g. for the gizmos.
+    pub fn always(self) -> Self {
+        Self {
+            always: true,
+            ..self
+        }
+    }
+}
+
+struct Line {
+    from: Vec3,
+    to: Vec3,
+    style: DebugStyle,
+}
+
+// Immediate mode lines and shapes for debugging, added by any system during the update and drawn
+// by the scene cameras after everything else. All of them go into one vertex buffer per frame.
+// Nothing is recorded while disabled, except for the shapes styled to be always drawn.
+pub struct DebugDraw {
+    enabled: bool,
+    lines: Vec<Line>,
+    depth_tested: MaterialHandle,
+    on_top: MaterialHandle,
+    vertices: Option<wgpu::Buffer>,
+    // Vertices of the depth tested and the on-top lines in the buffer
+    ranges: [Range<u32>; 2],
+}
+
+impl DebugDraw {
+    const CIRCLE_SEGMENTS: usize = 24;
+    // Relative to the arrow length
+    const ARROW_HEAD_SIZE: f32 = 0.2;
+
+    pub fn new(rr: &Renderer, assets: &mut Assets) -> Self {
+        let depth_tested = assets.add_material_with("debug_draw#depth_tested", |assets| {
+            materials::Material::debug_lines(rr, assets, true)
+        });
+        let on_top = assets.add_material_with("debug_draw#on_top", |assets| {
+            materials::Material::debug_lines(rr, assets, false)
+        });
+
+        Self {
+            enabled: false,
+            lines: Vec::new(),
+            depth_tested,
+            on_top,
+            vertices: None,
+            ranges: [0..0, 0..0],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.lines.clear();
+    }
+
+    // Drops the shapes that outlived their duration, must go before adding new ones.
+    pub fn update(&mut self, dt: f32) {
+        self.lines.retain_mut(|l| {
+            l.style.duration -= dt;
+            l.style.duration >= 0.0
+        });
+    }
+
+    pub fn line(&mut self, from: Vec3, to: Vec3, style: DebugStyle) {
+        if self.enabled || style.always {
+            self.lines.push(Line { from, to, style });
+        }
+    }
+
+    pub fn ray(&mut self, ray: &Ray, length: f32, style: DebugStyle) {
+        self.line(ray.origin.coords, ray.point_at(length).coords, style);
+    }
+
+    // With a head at the `to` end.
+    pub fn arrow(&mut self, from: Vec3, to: Vec3, style: DebugStyle) {
+        self.line(from, to, style);
+
+        let dir = to - from;
+        let length = dir.norm();
+        if length < f32::EPSILON {
+            return;
+        }
+        let (side, up) = perpendiculars(dir / length);
+        let size = length * Self::ARROW_HEAD_SIZE;
+        let base = to - dir * Self::ARROW_HEAD_SIZE;
+        for offset in [side, -side, up, -up] {
+            self.line(to, base + offset * size * 0.5, style);
+        }
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, style: DebugStyle) {
+        self.oriented_box(
+            &Mat4::new_translation(&aabb.center()),
+            (aabb.max - aabb.min) * 0.5,
+            style,
+        );
+    }
+
+    // Centered at the origin of the transform.
+    pub fn oriented_box(&mut self, transform: &Mat4, half_extents: Vec3, style: DebugStyle) {
+        let corner = |i: usize| {
+            let local = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            )
+            .component_mul(&half_extents);
+            transform.transform_point(&local.into()).coords
+        };
+        // Corners whose indices differ in one bit share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), style);
+                }
+            }
+        }
+    }
+
+    // As three circles around the axes.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, style: DebugStyle) {
+        for normal in [Vec3::x(), Vec3::y(), Vec3::z()] {
+            self.circle(center, normal, radius, style);
+        }
+    }
+
+    // In the plane perpendicular to the normal.
+    pub fn circle(&mut self, center: Vec3, normal: Vec3, radius: f32, style: DebugStyle) {
+        let (a, b) = perpendiculars(normal.normalize());
+        let point = |i: usize| {
+            let angle = TAU * i as f32 / Self::CIRCLE_SEGMENTS as f32;
+            center + (a * angle.cos() + b * angle.sin()) * radius
+        };
+        for i in 0..Self::CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), style);
+        }
+    }
+
+    // Axes of the transform, X red, Y green and Z blue. The style color is ignored, except for
+    // the alpha.
+    pub fn frame(&mut self, transform: &Mat4, size: f32, style: DebugStyle) {
+        let origin = transform.column(3).xyz();
+        for (axis, color) in [
+            (Vec3::x(), Vec3::new(1.0, 0.0, 0.0)),
+            (Vec3::y(), Vec3::new(0.0, 1.0, 0.0)),
+            (Vec3::z(), Vec3::new(0.0, 0.0, 1.0)),
+        ] {
+            let dir = transform.transform_vector(&axis).normalize();
+            let style = DebugStyle {
+                color: color.push(style.color.w),
+                ..style
+            };
+            self.line(origin, origin + dir * size, style);
+        }
+    }
+
+    // Uploads the lines of the frame, called once before the cameras render.
+    pub fn upload(&mut self, rr: &Renderer) {
+        let (depth_tested, on_top): (Vec<_>, Vec<_>) =
+            self.lines.iter().partition(|l| l.style.depth_test);
+        let vertices = depth_tested
+            .iter()
+            .chain(&on_top)
+            .flat_map(|l| {
+                let color = l.style.color.into();
+                [
+                    PositionColorVertex {
+                        position: l.from.into(),
+                        color,
+                    },
+                    PositionColorVertex {
+                        position: l.to.into(),
+                        color,
+                    },
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let split = depth_tested.len() as u32 * 2;
+        self.ranges = [0..split, split..vertices.len() as u32];
+        if vertices.is_empty() {
+            return;
+        }
+
+        let size = size_of_val(vertices.as_slice()) as u64;
+        if self.vertices.as_ref().is_none_or(|b| b.size() < size) {
+            self.vertices = Some(rr.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        rr.queue().write_buffer(
+            self.vertices.as_ref().unwrap(),
+            0,
+            bytemuck::cast_slice(&vertices),
+        );
+    }
+
+    // Rebuilt every frame, the lines change all the time anyway.
+    pub fn build_bundle(
+        &self,
+        rr: &Renderer,
+        assets: &Assets,
+        target: Option<&RenderTarget>,
+        scene_group: &wgpu::BindGroup,
+    ) -> Option<RenderBundle> {
+        if self.ranges.iter().all(|r| r.is_empty()) {
+            return None;
+        }
+
+        let [depth_tested, on_top] = self.ranges.clone();
+        Some(rr.build_lines_bundle(
+            self.vertices.as_ref().unwrap(),
+            &[
+                (assets.material(self.depth_tested).inner(), depth_tested),
+                (assets.material(self.on_top).inner(), on_top),
+            ],
+            target,
+            Some(scene_group),
+        ))
+    }
+}
+
+// Two unit vectors perpendicular to the direction and each other.
+fn perpendiculars(dir: Vec3) -> (Vec3, Vec3) {
+    let other = if dir.y.abs() < 0.9 {
+        Vec3::y()
+    } else {
+        Vec3::x()
+    };
+    let side = dir.cross(&other).normalize();
+    (side, dir.cross(&side))
+}
@@ -0,0 +1,67 @@
+use std::time::Instant;
+
+// Rolling per-frame values for the graphs, in the order they were first seen.
+#[derive(Default)]
+pub struct StatsHistory {
+    rows: Vec<(String, Vec<f32>)>,
+}
+
+impl StatsHistory {
+    const LENGTH: usize = 120;
+
+    pub fn push(&mut self, name: &str, value: f32) {
+        let values = match self.rows.iter_mut().position(|(n, _)| n == name) {
+            Some(idx) => &mut self.rows[idx].1,
+            None => {
+                self.rows
+                    .push((name.to_owned(), Vec::with_capacity(Self::LENGTH)));
+                &mut self.rows.last_mut().unwrap().1
+            }
+        };
+        if values.len() == Self::LENGTH {
+            values.remove(0);
+        }
+        values.push(value);
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = (&str, &[f32])> {
+        self.rows.iter().map(|(n, v)| (n.as_str(), v.as_slice()))
+    }
+}
+
+// What the stats window shows. GPU timings are in milliseconds per pass label, CPU ones per
+// update stage.
+#[derive(Default)]
+pub struct FrameStats {
+    pub gpu_supported: bool,
+    pub gpu: StatsHistory,
+    pub cpu: StatsHistory,
+    pub counts: StatsHistory,
+}
+
+// Measures consecutive stages, each from the end of the previous one.
+pub struct StageTimer {
+    last: Instant,
+    stages: Vec<(&'static str, f32)>,
+}
+
+impl StageTimer {
+    pub fn start() -> Self {
+        Self {
+            last: Instant::now(),
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn stage(&mut self, name: &'static str) {
+        let now = Instant::now();
+        self.stages
+            .push((name, (now - self.last).as_secs_f32() * 1000.0));
+        self.last = now;
+    }
+
+    // Milliseconds per stage.
+    pub fn finish(self) -> Vec<(&'static str, f32)> {
+        self.stages
+    }
+}
@@ -0,0 +1,222 @@
+use futures_lite::future;
+use gltf::image::Format;
+use gltf::material::AlphaMode;
+use gltf::texture::{MagFilter, WrappingMode};
+use hecs::World;
+
+use super::assets::{Assets, TextureHandle};
+use super::components::{Materials, Mesh, Transform};
+use super::materials;
+use crate::file;
+use crate::math::{Mat4, Vec3, Vec4};
+use crate::render;
+use crate::render::{BlendMode, Renderer, SamplerOptions, Texture};
+
+// Spawns an entity for every node with a mesh from the default scene of a glTF file.
+// There are no transform hierarchies yet, so each entity gets the node's world transform relative
+// to `root`.
+pub fn import_scene(
+    path: &str,
+    root: &Mat4,
+    world: &mut World,
+    rr: &Renderer,
+    assets: &mut Assets,
+) {
+    let (doc, buffers, images) = future::block_on(file::read_gltf_asset(path)).unwrap();
+    let importer = Importer {
+        path,
+        buffers: &buffers,
+        images: &images,
+    };
+
+    let scene = doc
+        .default_scene()
+        .or_else(|| doc.scenes().next())
+        .expect("glTF file without scenes");
+    for node in scene.nodes() {
+        importer.import_node(&node, root, world, rr, assets);
+    }
+}
+
+struct Importer<'a> {
+    path: &'a str,
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+}
+
+impl Importer<'_> {
+    fn import_node(
+        &self,
+        node: &gltf::Node,
+        parent: &Mat4,
+        world: &mut World,
+        rr: &Renderer,
+        assets: &mut Assets,
+    ) {
+        let matrix = parent * Mat4::from(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            // Entities have up to 4 materials, one per mesh part, so meshes with more
+            // primitives are split into several entities.
+            let primitives = mesh
+                .primitives()
+                .filter(|p| p.mode() == gltf::mesh::Mode::Triangles)
+                .collect::<Vec<_>>();
+            for (chunk_idx, chunk) in primitives.chunks(4).enumerate() {
+                let key = format!("{}#mesh{}#{chunk_idx}", self.path, mesh.index());
+                let handle = assets.try_add_mesh_with(&key, || {
+                    let data =
+                        render::MeshData::from_gltf_primitives(chunk.to_vec(), self.buffers)?;
+                    Ok(render::Mesh::new(rr, &data))
+                });
+                let handle = match handle {
+                    Ok(handle) => handle,
+                    Err(e) => {
+                        eprintln!("Skipping mesh {} of {}: {e}", mesh.index(), self.path);
+                        continue;
+                    }
+                };
+
+                let mats = chunk
+                    .iter()
+                    .map(|p| {
+                        let key = match p.material().index() {
+                            Some(idx) => format!("{}#material{idx}", self.path),
+                            None => format!("{}#default_material", self.path),
+                        };
+                        Some(assets.add_material_with(&key, |assets| {
+                            self.material(&p.material(), rr, assets)
+                        }))
+                    })
+                    .collect::<Vec<_>>();
+
+                world.spawn((
+                    Transform::from_matrix(&matrix),
+                    Mesh(handle),
+                    Materials([
+                        mats.first().copied().flatten(),
+                        mats.get(1).copied().flatten(),
+                        mats.get(2).copied().flatten(),
+                        mats.get(3).copied().flatten(),
+                    ]),
+                ));
+            }
+        }
+
+        for child in node.children() {
+            self.import_node(&child, &matrix, world, rr, assets);
+        }
+    }
+
+    fn material(
+        &self,
+        mat: &gltf::Material,
+        rr: &Renderer,
+        assets: &mut Assets,
+    ) -> materials::Material {
+        let pbr = mat.pbr_metallic_roughness();
+        let mut texture =
+            |tex: gltf::Texture, srgb: bool| self.texture(tex.source().index(), srgb, rr, assets);
+
+        let defaults = materials::PbrOptions::default();
+        let options = materials::PbrOptions {
+            base_color: Vec4::from(pbr.base_color_factor()),
+            base_color_texture: pbr.base_color_texture().map(|i| texture(i.texture(), true)),
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            metallic_roughness_texture: pbr
+                .metallic_roughness_texture()
+                .map(|i| texture(i.texture(), false)),
+            normal_texture: mat.normal_texture().map(|i| texture(i.texture(), false)),
+            normal_scale: mat.normal_texture().map_or(1.0, |i| i.scale()),
+            occlusion_texture: mat.occlusion_texture().map(|i| texture(i.texture(), false)),
+            occlusion_strength: mat.occlusion_texture().map_or(1.0, |i| i.strength()),
+            emissive: Vec3::from(mat.emissive_factor()),
+            emissive_texture: mat.emissive_texture().map(|i| texture(i.texture(), true)),
+            // Only one sampler per material, taken from the base color texture.
+            sampler: pbr.base_color_texture().map_or(defaults.sampler, |i| {
+                sampler_options(&i.texture().sampler())
+            }),
+            blend_mode: match mat.alpha_mode() {
+                AlphaMode::Opaque => BlendMode::Opaque,
+                AlphaMode::Blend => BlendMode::AlphaBlend,
+                // 0.5 is the glTF default
+                AlphaMode::Mask => BlendMode::Cutout(mat.alpha_cutoff().unwrap_or(0.5)),
+            },
+        };
+        materials::Material::pbr(rr, assets, &options)
+    }
+
+    // The same image can be used both as color and data, hence the separate keys.
+    fn texture(
+        &self,
+        image_idx: usize,
+        srgb: bool,
+        rr: &Renderer,
+        assets: &mut Assets,
+    ) -> TextureHandle {
+        let image = &self.images[image_idx];
+        let suffix = if srgb { "" } else { "#linear" };
+        assets.add_texture_with(
+            &format!("{}#image{}{}", self.path, image_idx, suffix),
+            || Texture::new_2d_from_rgba(rr, &to_rgba8(image), (image.width, image.height), srgb),
+        )
+    }
+}
+
+// Only one address mode is supported, so the U one is used for both.
+fn sampler_options(sampler: &gltf::texture::Sampler) -> SamplerOptions {
+    SamplerOptions {
+        filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+            _ => wgpu::FilterMode::Linear,
+        },
+        address_mode: match sampler.wrap_s() {
+            WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+            WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+            WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        },
+        ..SamplerOptions::default()
+    }
+}
+
+// Images are decoded by the gltf crate into whatever format they were stored in.
+fn to_rgba8(image: &gltf::image::Data) -> Vec<u8> {
+    let (channels, channel_size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    image
+        .pixels
+        .chunks_exact(channels * channel_size)
+        .flat_map(|px| {
+            let channel = |idx: usize| -> u8 {
+                let bytes = &px[idx * channel_size..(idx + 1) * channel_size];
+                match channel_size {
+                    1 => bytes[0],
+                    // Little-endian, taking the most significant byte
+                    2 => bytes[1],
+                    _ => {
+                        let v = f32::from_le_bytes(bytes.try_into().unwrap());
+                        (v.clamp(0.0, 1.0) * 255.0) as u8
+                    }
+                }
+            };
+            match channels {
+                1 => [channel(0), channel(0), channel(0), 255],
+                2 => [channel(0), channel(1), 0, 255],
+                3 => [channel(0), channel(1), channel(2), 255],
+                _ => [channel(0), channel(1), channel(2), channel(3)],
+            }
+        })
+        .collect()
+}
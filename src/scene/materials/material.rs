@@ -1,45 +1,215 @@
-use super::super::components::{Camera, Transform};
+use super::super::assets::TextureHandle;
 use super::super::Assets;
-use super::uniforms::{Vec3Uniform, ViewInvProjUniform, WorldViewProjUniform};
-use crate::math::Vec3;
+use super::uniforms::{ColorUniform, PbrUniform, PostProcessUniform, UvTransformUniform};
+use crate::math::{Vec2, Vec3, Vec4};
 use crate::render;
-use crate::render::{MaterialBuilder, PositionUvNormalVertex, PositionUvVertex, Renderer, Texture};
+use crate::render::{
+    BlendMode, MaterialBuilder, MeshMaterial, MeshVertex, PositionColorVertex, PositionUvVertex,
+    Renderer, SamplerOptions, Texture,
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct TexturedOptions {
+    pub sampler: SamplerOptions,
+    // Applied to the mesh UVs as `uv * scale + offset`, e.g. for tiling.
+    pub uv_scale: Vec2,
+    pub uv_offset: Vec2,
+    // Alpha comes from the texture
+    pub blend_mode: BlendMode,
+}
+
+impl Default for TexturedOptions {
+    fn default() -> Self {
+        Self {
+            sampler: SamplerOptions::default(),
+            uv_scale: Vec2::new(1.0, 1.0),
+            uv_offset: Vec2::zeros(),
+            blend_mode: BlendMode::Opaque,
+        }
+    }
+}
+
+// Metallic-roughness parameters, same as in glTF. Textures are multiplied by the factors,
+// missing ones count as white (flat for the normal map).
+#[derive(Copy, Clone, Debug)]
+pub struct PbrOptions {
+    pub base_color: Vec4,
+    pub base_color_texture: Option<TextureHandle>,
+    pub metallic: f32,
+    pub roughness: f32,
+    // Roughness in G, metallic in B
+    pub metallic_roughness_texture: Option<TextureHandle>,
+    pub normal_texture: Option<TextureHandle>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureHandle>,
+    pub occlusion_strength: f32,
+    pub emissive: Vec3,
+    pub emissive_texture: Option<TextureHandle>,
+    pub sampler: SamplerOptions,
+    // Alpha comes from the base color
+    pub blend_mode: BlendMode,
+}
+
+impl Default for PbrOptions {
+    fn default() -> Self {
+        Self {
+            base_color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            metallic: 0.0,
+            roughness: 0.5,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: Vec3::zeros(),
+            emissive_texture: None,
+            sampler: SamplerOptions::default(),
+            blend_mode: BlendMode::Opaque,
+        }
+    }
+}
+
+// A full-screen pass, see `post_process.wgsl` for the entry points.
+pub struct PostProcessOptions<'a> {
+    pub entry_point: &'static str,
+    // Meaning depends on the entry point
+    pub params: [f32; 4],
+    pub src: &'a Texture,
+    pub src_size: render::TextureSize,
+    // Second input, the source is bound again when there's none.
+    pub aux: Option<&'a Texture>,
+    pub color_format: wgpu::TextureFormat,
+}
 
 pub enum Material {
     Color(render::Material),
     Textured(render::Material),
+    Pbr(render::Material),
     Skybox(render::Material),
     PostProcess(render::Material),
+    DebugLines(render::Material),
+    Silhouette(render::Material),
 }
 
 impl Material {
-    pub fn textured(rr: &Renderer, assets: &mut Assets, tex_path: &str) -> Self {
+    pub fn textured(
+        rr: &Renderer,
+        assets: &mut Assets,
+        tex_path: &str,
+        options: &TexturedOptions,
+    ) -> Self {
+        let tex = assets.add_2d_texture_from_file(rr, tex_path, true);
+        Self::textured_from_handle(rr, assets, tex, options)
+    }
+
+    pub fn textured_from_handle(
+        rr: &Renderer,
+        assets: &mut Assets,
+        tex: TextureHandle,
+        options: &TexturedOptions,
+    ) -> Self {
         let shader = assets.add_shader_from_file(rr, "textured.wgsl");
-        let tex = assets.add_2d_texture_from_file(rr, tex_path);
         let material = MaterialBuilder::new()
-            .with_uniform(rr, WorldViewProjUniform::default())
+            .with_scene_bindings(rr)
             // TODO We shouldn't call assets again to get the actual objects, they should be returned
             // from the Assets' methods that created them.
-            .with_2d_texture(rr, assets.texture(tex))
-            .build::<PositionUvNormalVertex>(rr, assets.shader(shader));
+            .with_2d_texture_and_sampler(rr, assets.texture(tex), &options.sampler)
+            .with_uniform(
+                rr,
+                UvTransformUniform::new(options.uv_scale, options.uv_offset),
+            )
+            .blend_mode(options.blend_mode)
+            .build::<MeshVertex>(rr, assets.shader(shader));
         Self::Textured(material)
     }
 
-    pub fn post_process(rr: &Renderer, assets: &mut Assets, src_texture: &Texture) -> Self {
-        let shader = assets.add_shader_from_file(rr, "post_process.wgsl");
+    // Lit with the scene lights and the image based lighting from `Assets::ibl`.
+    pub fn pbr(rr: &Renderer, assets: &mut Assets, options: &PbrOptions) -> Self {
+        let shader = assets.add_shader_from_file(rr, "pbr.wgsl");
+        let white = assets.add_texture_with("builtin#white", || {
+            Texture::new_2d_from_rgba(rr, &[255; 4], (1, 1), true)
+        });
+        let white_linear = assets.add_texture_with("builtin#white_linear", || {
+            Texture::new_2d_from_rgba(rr, &[255; 4], (1, 1), false)
+        });
+        let flat_normal = assets.add_texture_with("builtin#flat_normal", || {
+            Texture::new_2d_from_rgba(rr, &[128, 128, 255, 255], (1, 1), false)
+        });
+
+        let textures = [
+            options.base_color_texture.unwrap_or(white),
+            options.metallic_roughness_texture.unwrap_or(white_linear),
+            options.normal_texture.unwrap_or(flat_normal),
+            options.occlusion_texture.unwrap_or(white_linear),
+            options.emissive_texture.unwrap_or(white),
+        ]
+        .map(|tex| assets.texture(tex));
+        let ibl = assets.ibl();
+
         let material = MaterialBuilder::new()
-            .with_2d_texture(rr, src_texture)
-            // TODO We shouldn't call assets again to get the actual objects, they should be returned
-            // from the Assets' methods that created them.
-            .build::<PositionUvVertex>(rr, assets.shader(shader));
+            .with_scene_bindings(rr)
+            .with_uniform_and_2d_textures(rr, PbrUniform::new(options), &textures, &options.sampler)
+            .with_textures(
+                rr,
+                &[
+                    (ibl.irradiance(), wgpu::TextureViewDimension::Cube),
+                    (ibl.prefiltered(), wgpu::TextureViewDimension::Cube),
+                    (ibl.brdf_lut(), wgpu::TextureViewDimension::D2),
+                ],
+            )
+            .blend_mode(options.blend_mode)
+            .build::<MeshVertex>(rr, assets.shader(shader));
+        Self::Pbr(material)
+    }
+
+    // The shader is `post_process.wgsl`, taken as is since the inputs may come from the assets.
+    pub fn post_process(
+        rr: &Renderer,
+        shader: &wgpu::ShaderModule,
+        options: &PostProcessOptions,
+    ) -> Self {
+        let textures = [options.src, options.aux.unwrap_or(options.src)];
+        let material = MaterialBuilder::new()
+            .with_uniform_and_2d_textures(
+                rr,
+                PostProcessUniform::new(options.params, options.src_size),
+                &textures,
+                // Anisotropic filtering would blur lookups with jumping coordinates, e.g. into LUTs
+                &SamplerOptions {
+                    address_mode: wgpu::AddressMode::ClampToEdge,
+                    anisotropy: 1,
+                    ..Default::default()
+                },
+            )
+            // Post-processing targets and the surface are single-sampled.
+            .sample_count(1)
+            .color_format(options.color_format)
+            .fragment_entry_point(options.entry_point)
+            .build::<PositionUvVertex>(rr, shader);
         Self::PostProcess(material)
     }
 
+    // Updates the params of a post-processing material in place.
+    pub fn set_post_process_params(
+        &self,
+        rr: &Renderer,
+        params: [f32; 4],
+        src_size: render::TextureSize,
+    ) {
+        self.inner()
+            .write_uniform(rr, PostProcessUniform::new(params, src_size));
+    }
+
     pub fn skybox(rr: &Renderer, assets: &mut Assets, tex_path: &str) -> Self {
-        let shader = assets.add_shader_from_file(rr, "skybox.wgsl");
         let tex = assets.add_cube_texture_from_file(rr, tex_path);
+        Self::skybox_from_handle(rr, assets, tex)
+    }
+
+    pub fn skybox_from_handle(rr: &Renderer, assets: &mut Assets, tex: TextureHandle) -> Self {
+        let shader = assets.add_shader_from_file(rr, "skybox.wgsl");
         let material = MaterialBuilder::new()
-            .with_uniform(rr, ViewInvProjUniform::default())
+            .with_scene_bindings(rr)
             // TODO We shouldn't call assets again to get the actual objects, they should be returned
             // from the Assets' methods that created them.
             .with_cube_texture(rr, assets.texture(tex))
@@ -48,38 +218,77 @@ impl Material {
         Self::Skybox(material)
     }
 
-    pub fn color(rr: &Renderer, assets: &mut Assets, color: Vec3, wireframe: bool) -> Self {
+    pub fn color(
+        rr: &Renderer,
+        assets: &mut Assets,
+        color: Vec4,
+        wireframe: bool,
+        blend_mode: BlendMode,
+    ) -> Self {
         let shader = assets.add_shader_from_file(rr, "color.wgsl");
         let material = MaterialBuilder::new()
-            .with_uniform(rr, WorldViewProjUniform::default())
-            .with_uniform(rr, Vec3Uniform::new(color))
+            .with_scene_bindings(rr)
+            .with_uniform(rr, ColorUniform::new(color, !wireframe))
             .wireframe(wireframe)
+            .blend_mode(blend_mode)
             // TODO Leaner vertex format. Can't use it currently because this material
             // is used for file-loaded meshes where we currently only support a single vertex format.
             // TODO We shouldn't call assets again to get the actual objects, they should be returned
             // from the Assets' methods that created them.
-            .build::<PositionUvNormalVertex>(rr, assets.shader(shader));
+            .build::<MeshVertex>(rr, assets.shader(shader));
         Self::Color(material)
     }
 
-    pub fn update(&self, rr: &Renderer, cam: &Camera, cam_tr: &Transform, tr: &Transform) {
-        match self {
-            Material::Color(m) => m.update_buffer(
-                rr,
-                0,
-                WorldViewProjUniform::new(&tr.matrix(), &cam_tr.view_matrix(), &cam.proj_matrix()),
-            ),
-            Material::Textured(m) => m.update_buffer(
-                rr,
-                0,
-                WorldViewProjUniform::new(&tr.matrix(), &cam_tr.view_matrix(), &cam.proj_matrix()),
-            ),
-            Material::Skybox(m) => m.update_buffer(
+    // Flat color without the depth test for drawing masks of whole objects, e.g. for outlines.
+    pub fn silhouette(
+        rr: &Renderer,
+        assets: &mut Assets,
+        color: Vec4,
+        color_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader = assets.add_shader_from_file(rr, "color.wgsl");
+        let material = MaterialBuilder::new()
+            .with_scene_bindings(rr)
+            .with_uniform(rr, ColorUniform::new(color, false))
+            .depth_test(false)
+            .depth_write(false)
+            .sample_count(1)
+            .color_format(color_format)
+            .build::<MeshVertex>(rr, assets.shader(shader));
+        Self::Silhouette(material)
+    }
+
+    pub fn set_silhouette_color(&self, rr: &Renderer, color: Vec4) {
+        self.inner()
+            .write_uniform(rr, ColorUniform::new(color, false));
+    }
+
+    // Line lists with vertex colors, see `DebugDraw`. Without the depth test the lines show
+    // through everything.
+    pub fn debug_lines(rr: &Renderer, assets: &mut Assets, depth_test: bool) -> Self {
+        let shader = assets.add_shader_from_file(rr, "debug_draw.wgsl");
+        let material = MaterialBuilder::new()
+            .with_scene_bindings(rr)
+            .wireframe(true)
+            .depth_test(depth_test)
+            .blend_mode(BlendMode::AlphaBlend)
+            .build::<PositionColorVertex>(rr, assets.shader(shader));
+        Self::DebugLines(material)
+    }
+
+    // Converts the material that came with a mesh file.
+    pub fn from_mesh_material(rr: &Renderer, assets: &mut Assets, mat: &MeshMaterial) -> Self {
+        if let Some(tex_path) = &mat.diffuse_texture {
+            Self::textured(rr, assets, tex_path, &TexturedOptions::default())
+        } else {
+            let [r, g, b] = mat.diffuse_color.unwrap_or([1.0, 1.0, 1.0]);
+            Self::color(
                 rr,
-                0,
-                ViewInvProjUniform::new(&cam_tr.view_matrix(), &cam.proj_matrix()),
-            ),
-            Material::PostProcess(_) => (),
+                assets,
+                Vec4::new(r, g, b, 1.0),
+                false,
+                BlendMode::Opaque,
+            )
         }
     }
 
@@ -87,8 +296,11 @@ impl Material {
         match self {
             Material::Color(m) => m,
             Material::Textured(m) => m,
+            Material::Pbr(m) => m,
             Material::Skybox(m) => m,
             Material::PostProcess(m) => m,
+            Material::DebugLines(m) => m,
+            Material::Silhouette(m) => m,
         }
     }
 }
@@ -1,4 +1,5 @@
 mod material;
 mod uniforms;
 
-pub use material::Material;
+pub use material::{Material, PbrOptions, PostProcessOptions, TexturedOptions};
+pub use uniforms::{CameraUniform, LightsUniform, ObjectUniform};
@@ -1,72 +1,212 @@
-use crate::math::{Mat4, Vec3};
+use super::super::components::{Light, LightKind, Transform};
+use super::PbrOptions;
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
 use crate::render;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Vec3Uniform([f32; 3]);
+pub struct ColorUniform {
+    color: [f32; 4],
+    // Wireframes have no meaningful normals, so they are left unlit.
+    lit: u32,
+    _padding: [u32; 3],
+}
 
-impl Vec3Uniform {
-    pub fn new(v: Vec3) -> Self {
-        Self([v.x, v.y, v.z])
+impl ColorUniform {
+    pub fn new(color: Vec4, lit: bool) -> Self {
+        Self {
+            color: color.into(),
+            lit: lit as u32,
+            _padding: [0; 3],
+        }
     }
 }
 
-impl Default for Vec3Uniform {
-    fn default() -> Self {
-        Self([0.0, 0.0, 1.0])
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UvTransformUniform {
+    scale: [f32; 2],
+    offset: [f32; 2],
+}
+
+impl UvTransformUniform {
+    pub fn new(scale: Vec2, offset: Vec2) -> Self {
+        Self {
+            scale: scale.into(),
+            offset: offset.into(),
+        }
     }
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct WorldViewProjUniform {
-    world: [[f32; 4]; 4],
-    view_proj: [[f32; 4]; 4],
+pub struct PbrUniform {
+    base_color: [f32; 4],
+    emissive: [f32; 3],
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    _padding: f32,
 }
 
-impl WorldViewProjUniform {
-    pub fn new(world: &Mat4, view: &Mat4, proj: &Mat4) -> Self {
+impl PbrUniform {
+    pub fn new(options: &PbrOptions) -> Self {
         Self {
-            world: (*world).into(),
-            view_proj: (render::WGPU_CONVERSION_MATRIX * proj * view).into(),
+            base_color: options.base_color.into(),
+            emissive: options.emissive.into(),
+            metallic: options.metallic,
+            roughness: options.roughness,
+            normal_scale: options.normal_scale,
+            occlusion_strength: options.occlusion_strength,
+            _padding: 0.0,
         }
     }
 }
 
-impl Default for WorldViewProjUniform {
-    fn default() -> Self {
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostProcessUniform {
+    params: [f32; 4],
+    texel_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl PostProcessUniform {
+    pub fn new(params: [f32; 4], src_size: render::TextureSize) -> Self {
         Self {
-            world: Mat4::identity().into(),
-            view_proj: Mat4::identity().into(),
+            params,
+            texel_size: [1.0 / src_size.0 as f32, 1.0 / src_size.1 as f32],
+            _padding: [0.0; 2],
         }
     }
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct ViewInvProjUniform {
-    // Couldn't make it work with Matrix3, probably something to do with alignment and padding
-    view_mat: [[f32; 4]; 4],
-    proj_mat_inv: [[f32; 4]; 4],
+pub struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+    // The skybox needs these separately.
+    view: [[f32; 4]; 4],
+    proj_inv: [[f32; 4]; 4],
 }
 
-impl ViewInvProjUniform {
+impl CameraUniform {
     pub fn new(view: &Mat4, proj: &Mat4) -> Self {
+        let proj = render::WGPU_CONVERSION_MATRIX * proj;
         Self {
-            view_mat: (*view).into(),
-            proj_mat_inv: (render::WGPU_CONVERSION_MATRIX * proj)
+            view_proj: (proj * view).into(),
+            view: (*view).into(),
+            proj_inv: proj.try_inverse().unwrap().into(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ObjectUniform {
+    world: [[f32; 4]; 4],
+    // Inverse transpose of the world matrix, keeps normals correct under non-uniform scaling.
+    normal: [[f32; 4]; 4],
+    receives_shadows: u32,
+    _padding: [u32; 3],
+}
+
+impl ObjectUniform {
+    pub fn new(world: &Mat4, receives_shadows: bool) -> Self {
+        Self {
+            world: (*world).into(),
+            normal: world
                 .try_inverse()
-                .unwrap()
+                .unwrap_or_else(Mat4::identity)
+                .transpose()
                 .into(),
+            receives_shadows: receives_shadows as u32,
+            _padding: [0; 3],
         }
     }
 }
 
-impl Default for ViewInvProjUniform {
-    fn default() -> Self {
-        Self {
-            view_mat: Mat4::identity().into(),
-            proj_mat_inv: Mat4::identity().into(),
+// Must match `lighting.wgsl`.
+const MAX_LIGHTS: usize = 8;
+const LIGHT_DIRECTIONAL: u32 = 0;
+const LIGHT_POINT: u32 = 1;
+const LIGHT_SPOT: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniform {
+    position: [f32; 4],
+    direction: [f32; 4],
+    // Premultiplied by the intensity
+    color: [f32; 4],
+    kind: u32,
+    range: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsUniform {
+    camera_pos: [f32; 4],
+    ambient: [f32; 4],
+    shadow_view_proj: [[f32; 4]; 4],
+    count: u32,
+    // Index of the light casting shadows, `NO_SHADOWS` if there's none.
+    shadow_light: u32,
+    _padding: [u32; 2],
+    lights: [LightUniform; MAX_LIGHTS],
+}
+
+const NO_SHADOWS: u32 = u32::MAX;
+
+impl LightsUniform {
+    // Lights over `MAX_LIGHTS` are ignored. The first directional light casts shadows using
+    // the `shadow_view_proj` matrix.
+    pub fn new<'a>(
+        camera_pos: Vec3,
+        ambient: Vec3,
+        shadow_view_proj: Option<Mat4>,
+        lights: impl Iterator<Item = (&'a Light, &'a Transform)>,
+    ) -> Self {
+        let mut res = Self {
+            camera_pos: camera_pos.push(1.0).into(),
+            ambient: ambient.push(1.0).into(),
+            shadow_view_proj: shadow_view_proj.unwrap_or_else(Mat4::identity).into(),
+            shadow_light: NO_SHADOWS,
+            ..Default::default()
+        };
+
+        for (light, tr) in lights.take(MAX_LIGHTS) {
+            if shadow_view_proj.is_some()
+                && res.shadow_light == NO_SHADOWS
+                && matches!(light.kind, LightKind::Directional)
+            {
+                res.shadow_light = res.count;
+            }
+
+            let (kind, range, cos_inner, cos_outer) = match light.kind {
+                LightKind::Directional => (LIGHT_DIRECTIONAL, 0.0, 0.0, 0.0),
+                LightKind::Point { range } => (LIGHT_POINT, range, 0.0, 0.0),
+                LightKind::Spot {
+                    range,
+                    inner_angle,
+                    outer_angle,
+                } => (LIGHT_SPOT, range, inner_angle.cos(), outer_angle.cos()),
+            };
+            res.lights[res.count as usize] = LightUniform {
+                position: tr.position().push(1.0).into(),
+                direction: tr.forward().normalize().push(0.0).into(),
+                color: (light.color * light.intensity).push(1.0).into(),
+                kind,
+                range,
+                cos_inner,
+                cos_outer,
+            };
+            res.count += 1;
         }
+
+        res
     }
 }
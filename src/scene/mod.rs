@@ -1,9 +1,15 @@
 mod assets;
 mod components;
+mod debug_draw;
+mod frame_stats;
+mod gltf_import;
 mod materials;
+#[allow(clippy::module_inception)]
 mod scene;
 mod scene_config;
 
 pub use assets::{Assets, MaterialHandle, MeshHandle};
-pub use scene::Scene;
+pub use debug_draw::{DebugDraw, DebugStyle};
+pub use frame_stats::FrameStats;
+pub use scene::{RenderStats, Scene};
 pub use scene_config::SceneCfg;
@@ -1,24 +1,68 @@
-use hecs::World;
+use hecs::{Entity, World};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::time::Instant;
 
-use crate::math::Vec3;
+use crate::input::InputAction;
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
 use crate::physics::Physics;
 use crate::render;
-use crate::render::{Renderer, Ui};
+use crate::render::{Frustum, MeshVertex, RenderBundle, Renderer, SceneBindings, ShadowPass, Ui};
 use crate::state::AppState;
 
-use super::assets::Assets;
+use super::assets::{Assets, MaterialHandle, MeshHandle};
 use super::components::{
-    Camera, Grab, Hud, Materials, Mesh, Player, PlayerFocusMarker, PostProcess, RenderOrder,
-    RenderTags, RigidBody, Transform, RENDER_TAG_SCENE,
+    Camera, Gizmo, Grab, Hud, Light, LightKind, Materials, Mesh, NoCulling, Outline, PhysicsDebug,
+    Player, PlayerFocusMarker, PostEffect, PostEffectKind, PostProcess, RenderOrder, RenderTags,
+    RigidBody, Shadows, TonemapOperator, Tonemapping, Transform, RENDER_TAG_SCENE,
 };
-use super::scene_config::{ComponentCfg, MaterialCfg, MeshPrefabCfg, SceneCfg};
-use super::{components, materials};
+use super::debug_draw::{DebugDraw, DebugStyle};
+use super::frame_stats::{FrameStats, StageTimer};
+use super::scene_config::{
+    BlendModeCfg, ComponentCfg, LightCfg, MaterialCfg, MeshPrefabCfg, NodeCfg, PostEffectCfg,
+    SceneCfg, TextureAddressModeCfg, TextureFilterCfg, TonemapOperatorCfg,
+};
+use super::{components, gltf_import, materials};
 
 pub struct Scene {
     world: World,
     physics: Physics,
     ui: Ui,
     assets: Assets,
+    ambient: Vec3,
+    shadow_pass: ShadowPass,
+    scene_bindings: SceneBindings,
+    // Keyed by camera and draw.
+    bundles: HashMap<(Entity, DrawKey), CachedBundle>,
+    frame: u64,
+    render_stats: RenderStats,
+    frame_stats: FrameStats,
+    ui_visible: bool,
+    debug_draw: DebugDraw,
+}
+
+// Entities with the same render order, mesh and materials are drawn as instances of one batch.
+type BatchKey = (i32, MeshHandle, [Option<MaterialHandle>; 4]);
+// Transparent entities aren't batched, those sharing a batch key are told apart by their
+// position in the back-to-front order. Zero for opaque batches.
+type DrawKey = (BatchKey, u32);
+
+// A render bundle stays valid while its instances and bindings don't change.
+struct CachedBundle {
+    bundle: RenderBundle,
+    instances: Range<u32>,
+    generation: u32,
+    frame: u64,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    // What the draw calls would be without batching, one per entity and mesh part.
+    pub unbatched_draw_calls: u32,
+    // Entities, summed over all cameras.
+    pub drawn: u32,
+    pub culled: u32,
 }
 
 impl Scene {
@@ -34,19 +78,54 @@ impl Scene {
             Vec3::new(7.0, 7.0, 7.0),
         );
         PostProcess::spawn(&mut world, &state.renderer, &mut assets);
+        PhysicsDebug::spawn(&mut world);
+        Gizmo::spawn(&mut world);
+
+        let ui = Ui::new(state.window.as_deref(), &state.renderer);
+
+        let ambient = Vec3::from_element(0.1);
+        assets.set_environment(&state.renderer, None, ambient);
+
+        let shadow_shader = assets.add_shader_from_file(&state.renderer, "shadow.wgsl");
+        let shadow_pass = ShadowPass::new(&state.renderer, assets.shader(shadow_shader));
+        let scene_bindings = SceneBindings::new(
+            &state.renderer,
+            size_of::<materials::CameraUniform>(),
+            size_of::<materials::LightsUniform>(),
+            size_of::<materials::ObjectUniform>(),
+        );
 
-        let ui = Ui::new(&state.window, &state.renderer);
+        let debug_draw = DebugDraw::new(&state.renderer, &mut assets);
 
         Self {
             world,
             physics,
             ui,
             assets,
+            ambient,
+            shadow_pass,
+            scene_bindings,
+            bundles: HashMap::new(),
+            frame: 0,
+            render_stats: RenderStats::default(),
+            frame_stats: FrameStats {
+                gpu_supported: state.renderer.gpu_timings().is_some(),
+                ..FrameStats::default()
+            },
+            ui_visible: true,
+            debug_draw,
         }
     }
 
     pub fn update(&mut self, dt: f32, state: &AppState) {
+        let mut timer = StageTimer::start();
+        self.debug_draw.update(dt);
+        if state.input.action_activated(InputAction::ToggleDebugDraw) {
+            self.debug_draw.toggle();
+        }
+
         self.physics.update(dt);
+        timer.stage("Physics");
 
         Player::update(
             dt,
@@ -54,21 +133,84 @@ impl Scene {
             &mut self.world,
             &mut self.physics,
             &mut self.assets,
+            &mut self.debug_draw,
+        );
+        timer.stage("Player");
+        // Dragging a gizmo handle takes the grab button
+        let dragging = Gizmo::update(
+            &self.world,
+            &state.input,
+            &mut self.physics,
+            &mut self.debug_draw,
         );
-        Grab::update(&mut self.world, &state.input, &mut self.physics);
+        if !dragging {
+            Grab::update(
+                &mut self.world,
+                &state.input,
+                &mut self.physics,
+                &mut self.debug_draw,
+            );
+        }
         PlayerFocusMarker::update(&mut self.world);
+        timer.stage("Gizmo and grab");
         PostProcess::update(&mut self.world, &state.renderer, &mut self.assets);
+        timer.stage("Post-processing");
 
         self.sync_physics();
+        PhysicsDebug::update(&self.world, &self.physics, &mut self.debug_draw);
+        timer.stage("Physics sync");
 
-        for e in state.input.new_raw_events() {
-            self.ui.handle_event(e, &state.window);
+        if let Some(window) = &state.window {
+            for e in state.input.new_raw_events() {
+                self.ui.handle_event(e, window);
+            }
         }
 
-        Hud::update(dt, &mut self.world, state, &mut self.ui);
+        if state.input.action_activated(InputAction::ToggleUi) {
+            self.toggle_ui();
+        }
+        // A hidden UI doesn't even start a frame, there would be nothing to end it
+        if self.ui_visible {
+            Hud::update(
+                dt,
+                &mut self.world,
+                state,
+                &self.render_stats,
+                &self.frame_stats,
+                &mut self.debug_draw,
+                &mut self.ui,
+            );
+        }
+        timer.stage("UI");
+
+        for (stage, ms) in timer.finish() {
+            self.frame_stats.cpu.push(stage, ms);
+        }
+    }
+
+    pub fn toggle_ui(&mut self) {
+        self.ui_visible = !self.ui_visible;
+    }
+
+    // Moves the player camera, e.g. to render the same view every time.
+    #[cfg(test)]
+    pub fn look_from(&mut self, pos: Vec3, target: Vec3) {
+        let (_, tr) = self
+            .world
+            .query_mut::<&mut Transform>()
+            .with::<&Player>()
+            .into_iter()
+            .next()
+            .unwrap();
+        tr.set_position(pos);
+        tr.look_at(target);
     }
 
     pub fn render(&mut self, rr: &Renderer) {
+        let start = Instant::now();
+        self.frame += 1;
+        let shadow_view_proj = self.render_shadows(rr);
+
         let mut cameras = self
             .world
             .query::<(&Camera, &Transform, Option<&RenderOrder>)>();
@@ -82,184 +224,794 @@ impl Scene {
                 .unwrap()
         });
 
-        for (_, (cam, cam_tr, _)) in cameras {
-            let mut items = self.world.query::<(
-                &Mesh,
-                &Materials,
-                &Transform,
-                Option<&RenderOrder>,
-                Option<&RenderTags>,
-            )>();
-
-            // Pick what should be rendered by the camera
-            let mut items = items
-                .iter()
-                .filter(|(_, (.., tag))| {
-                    cam.should_render(tag.unwrap_or(&RenderTags(RENDER_TAG_SCENE)).0)
-                })
-                .map(|(_, (mesh, mats, tr, order, _))| (mesh, mats, tr, order))
-                .collect::<Vec<_>>();
+        // Group what each camera sees into batches drawn as one instanced draw call per mesh
+        // part. Objects of all the batches are laid out one after another in the storage buffer.
+        let mut objects = Vec::new();
+        let mut stats = RenderStats::default();
+        let camera_batches = cameras
+            .iter()
+            .map(|(_, (cam, cam_tr, _))| {
+                let frustum = Frustum::from_view_proj(&(cam.proj_matrix() * cam_tr.view_matrix()));
+                let mut batches = BTreeMap::<BatchKey, Vec<materials::ObjectUniform>>::new();
+                let mut transparent = Vec::new();
+                let mut items = self.world.query::<(
+                    &Mesh,
+                    &Materials,
+                    &Transform,
+                    Option<&RenderOrder>,
+                    Option<&RenderTags>,
+                    Option<&Shadows>,
+                    Option<&NoCulling>,
+                )>();
+                for (_, (mesh, mats, tr, order, tag, shadows, no_culling)) in items.iter() {
+                    if !cam.should_render(tag.unwrap_or(&RenderTags(RENDER_TAG_SCENE)).0) {
+                        continue;
+                    }
+                    let bounds = tr.world_bounds(self.assets.mesh(mesh.0).bounds());
+                    if no_culling.is_none() && !frustum.intersects(&bounds) {
+                        stats.culled += 1;
+                        continue;
+                    }
+                    stats.drawn += 1;
+                    // Ordering by the key keeps the batches sorted by render order
+                    let key = (order.unwrap_or(&RenderOrder(0)).0, mesh.0, mats.0);
+                    let object = materials::ObjectUniform::new(
+                        &tr.matrix(),
+                        shadows.is_none_or(|s| s.receive),
+                    );
+                    let is_transparent = mats.0.iter().flatten().any(|&mat| {
+                        self.assets
+                            .material(mat)
+                            .inner()
+                            .blend_mode()
+                            .is_transparent()
+                    });
+                    if is_transparent {
+                        let distance = (bounds.center() - cam_tr.position()).norm_squared();
+                        transparent.push((key, distance, object));
+                    } else {
+                        batches.entry(key).or_default().push(object);
+                    }
+                }
+
+                // Transparent entities go after all the opaque ones, farthest first within
+                // the same render order.
+                transparent.sort_by(|(key1, dist1, _), (key2, dist2, _)| {
+                    key1.0.cmp(&key2.0).then(dist2.total_cmp(dist1))
+                });
+                let transparent = transparent
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, (key, _, object))| ((key, idx as u32), vec![object]));
+
+                batches
+                    .into_iter()
+                    .map(|(key, batch)| ((key, 0), batch))
+                    .chain(transparent)
+                    .map(|(key, batch)| {
+                        let parts = self.assets.mesh(key.0 .1).parts_count();
+                        stats.draw_calls += parts;
+                        stats.unbatched_draw_calls += parts * batch.len() as u32;
+
+                        let first = objects.len() as u32;
+                        objects.extend(batch);
+                        (key, first..objects.len() as u32)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // The outlined entities go after the cameras' objects, drawn into the outline mask
+        let outlined = self
+            .outlined_entities()
+            .into_iter()
+            .filter_map(|(entity, kind)| {
+                let (mesh, tr, shadows) = self
+                    .world
+                    .query_one::<(&Mesh, &Transform, Option<&Shadows>)>(entity)
+                    .ok()?
+                    .get()
+                    .map(|(m, t, s)| (m.0, t.matrix(), s.is_none_or(|s| s.receive)))?;
+                let first = objects.len() as u32;
+                objects.push(materials::ObjectUniform::new(&tr, shadows));
+                Some((mesh, kind, first..first + 1))
+            })
+            .collect::<Vec<_>>();
+        self.scene_bindings.update_objects(rr, &objects);
+        self.render_stats = stats;
+        self.debug_draw.upload(rr);
 
-            // Sort by render order
-            items.sort_by(|&(.., ro1), &(.., ro2)| {
-                ro1.unwrap_or(&RenderOrder(0))
-                    .0
-                    .partial_cmp(&ro2.unwrap_or(&RenderOrder(0)).0)
-                    .unwrap()
-            });
-
-            let bundles = items
-                .into_iter()
-                .map(|(mesh, mats, tr, _)| {
-                    let mats = mats
-                        .0
+        for ((cam_entity, (cam, cam_tr, _)), batches) in cameras.into_iter().zip(camera_batches) {
+            self.scene_bindings.update_camera(
+                rr,
+                materials::CameraUniform::new(&cam_tr.view_matrix(), &cam.proj_matrix()),
+                materials::LightsUniform::new(
+                    cam_tr.position(),
+                    self.ambient,
+                    shadow_view_proj,
+                    self.world
+                        .query::<(&Light, &Transform)>()
                         .iter()
-                        .flatten()
-                        .map(|&mat| {
-                            let mat = self.assets.material(mat);
-                            mat.update(rr, cam, cam_tr, tr);
-                            mat.inner()
-                        })
-                        .collect::<Vec<_>>();
-                    let mesh = self.assets.mesh(mesh.0);
-                    rr.build_render_bundle(mesh, &mats, cam.target().as_ref())
-                })
-                .collect::<Vec<wgpu::RenderBundle>>();
+                        .map(|(_, l)| l),
+                ),
+            );
+
+            // Rebuild the bundles whose instances or bindings changed
+            let generation = self.scene_bindings.generation();
+            for (key, instances) in &batches {
+                let cached = self.bundles.get_mut(&(cam_entity, *key));
+                if let Some(cached) = cached
+                    && cached.instances == *instances
+                    && cached.generation == generation
+                {
+                    cached.frame = self.frame;
+                    continue;
+                }
+
+                let ((_, mesh, mats), _) = key;
+                let mat_refs = mats
+                    .iter()
+                    .flatten()
+                    .map(|&mat| self.assets.material(mat).inner())
+                    .collect::<Vec<_>>();
+                let bundle = rr.build_render_bundle(
+                    self.assets.mesh(*mesh),
+                    &mat_refs,
+                    cam.target().as_ref(),
+                    Some(self.scene_bindings.bind_group()),
+                    instances.clone(),
+                );
+                self.bundles.insert(
+                    (cam_entity, *key),
+                    CachedBundle {
+                        bundle,
+                        instances: instances.clone(),
+                        generation,
+                        frame: self.frame,
+                    },
+                );
+            }
+
+            // Debug lines go on top of the scene, post-processing cameras don't get them
+            let debug_bundle = if cam.target().is_some() && cam.should_render(RENDER_TAG_SCENE) {
+                self.debug_draw.build_bundle(
+                    rr,
+                    &self.assets,
+                    cam.target().as_ref(),
+                    self.scene_bindings.bind_group(),
+                )
+            } else {
+                None
+            };
+            let bundles = batches
+                .iter()
+                .map(|(key, _)| &self.bundles[&(cam_entity, *key)].bundle)
+                .chain(&debug_bundle)
+                .collect::<Vec<_>>();
 
+            if let Ok(pp) = self.world.get::<&PostProcess>(cam_entity) {
+                pp.render_passes(rr);
+            }
+            // Names the pass in the GPU timings
+            let label = if self.world.satisfies::<&Player>(cam_entity).unwrap_or(false) {
+                "Player camera"
+            } else if self
+                .world
+                .satisfies::<&PostProcess>(cam_entity)
+                .unwrap_or(false)
+            {
+                "Post-processing output"
+            } else {
+                "Camera"
+            };
             rr.render_pass(
+                label,
                 &bundles,
                 cam.target().as_ref(),
-                cam.target().is_none().then_some(&mut self.ui),
+                (cam.target().is_none() && self.ui_visible).then_some(&mut self.ui),
             );
+
+            // The mask is drawn with the player camera's uniform, still bound at this point
+            if self.world.satisfies::<&Player>(cam_entity).unwrap_or(false) {
+                self.render_outline_mask(rr, &outlined);
+            }
         }
+
+        // Drop the bundles of batches and cameras that are gone
+        self.bundles.retain(|_, b| b.frame == self.frame);
+
+        let counts = rr.end_frame();
+        let stats = &mut self.frame_stats;
+        stats.counts.push("Draw calls", counts.draw_calls as f32);
+        stats.counts.push("Bundles", counts.bundles as f32);
+        stats.counts.push("Triangles", counts.triangles as f32);
+        stats.counts.push("Pipelines", counts.pipelines as f32);
+        stats.counts.push("Bind groups", counts.bind_groups as f32);
+        for (pass, ms) in rr.gpu_timings().unwrap_or_default() {
+            stats.gpu.push(&pass, ms);
+        }
+        stats
+            .cpu
+            .push("Render", start.elapsed().as_secs_f32() * 1000.0);
+    }
+
+    // The hovered and the grabbed entity, with the index of their outline color (hover, grab).
+    // The grab wins when it's the same entity.
+    fn outlined_entities(&self) -> Vec<(Entity, usize)> {
+        let grabbed = self.world.query::<&Grab>().iter().next().map(|(e, _)| e);
+        let focus = self
+            .world
+            .query::<&Player>()
+            .iter()
+            .next()
+            .and_then(|(_, p)| p.focus());
+        let hovered = focus.and_then(|f| {
+            self.world
+                .query::<&RigidBody>()
+                .iter()
+                .find(|(_, b)| b.handle() == f.body)
+                .map(|(e, _)| e)
+        });
+
+        hovered
+            .filter(|&e| Some(e) != grabbed)
+            .map(|e| (e, 0))
+            .into_iter()
+            .chain(grabbed.map(|e| (e, 1)))
+            .collect()
+    }
+
+    // Silhouettes of the outlined entities for the outline post-effect. Cleared even when there
+    // are none, the effect reads the mask every frame.
+    fn render_outline_mask(&self, rr: &Renderer, outlined: &[(MeshHandle, usize, Range<u32>)]) {
+        let mut pp = self.world.query::<&PostProcess>();
+        let Some((mask, silhouettes)) = pp.iter().next().and_then(|(_, pp)| pp.outline_mask())
+        else {
+            return;
+        };
+
+        let bundles = outlined
+            .iter()
+            .map(|(mesh, kind, instances)| {
+                // One material for all the parts, the bundle reuses the last one
+                rr.build_render_bundle(
+                    self.assets.mesh(*mesh),
+                    &[self.assets.material(silhouettes[*kind]).inner()],
+                    Some(mask),
+                    Some(self.scene_bindings.bind_group()),
+                    instances.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        rr.render_pass(
+            "Outline mask",
+            &bundles.iter().collect::<Vec<_>>(),
+            Some(mask),
+            None,
+        );
+    }
+
+    // Returns the view-projection matrix of the shadow-casting light, if there's one. The shadow
+    // map covers the area around the player.
+    fn render_shadows(&mut self, rr: &Renderer) -> Option<Mat4> {
+        let center = self
+            .world
+            .query::<(&Player, &Transform)>()
+            .iter()
+            .next()
+            .map_or(Vec3::zeros(), |(_, (_, tr))| tr.position());
+        let view_proj = self
+            .world
+            .query::<(&Light, &Transform)>()
+            .iter()
+            .find(|(_, (light, _))| matches!(light.kind, LightKind::Directional))
+            .map(|(_, (_, tr))| Light::shadow_view_proj(tr, center));
+
+        let mut casters = self.world.query::<(
+            &Mesh,
+            &Materials,
+            &Transform,
+            Option<&RenderTags>,
+            Option<&Shadows>,
+        )>();
+        let casters = view_proj.map_or(Vec::new(), |view_proj| {
+            casters
+                .iter()
+                .filter(|(_, (.., tag, shadows))| {
+                    tag.is_none_or(|t| t.0 == RENDER_TAG_SCENE) && shadows.is_none_or(|s| s.cast)
+                })
+                .map(|(_, (mesh, _, tr, ..))| (self.assets.mesh(mesh.0), tr))
+                // The depth pipeline takes `MeshVertex`, which leaves out e.g. the skybox quad
+                .filter(|(mesh, _)| mesh.has_vertex_type::<MeshVertex>())
+                .map(|(mesh, tr)| (mesh, view_proj * tr.matrix()))
+                .collect()
+        });
+        self.shadow_pass.render(rr, &casters);
+
+        view_proj
     }
 
     // TODO Try to make all initialization to happen via this func.
     pub fn insert_from_cfg(&mut self, cfg: &SceneCfg, state: &AppState) {
+        if let Some(env) = &cfg.environment {
+            self.ambient = Vec3::from(env.ambient);
+            self.assets
+                .set_environment(&state.renderer, env.map.as_deref(), self.ambient);
+            if let Some(effects) = &env.post_process {
+                PostProcess::set_effects(
+                    &mut self.world,
+                    effects.iter().map(post_effect_from_cfg).collect(),
+                );
+            }
+            if let Some(cfg) = &env.tonemapping {
+                let defaults = Tonemapping::default();
+                let tonemapping = Tonemapping {
+                    operator: match cfg.operator {
+                        Some(TonemapOperatorCfg::None) => TonemapOperator::None,
+                        Some(TonemapOperatorCfg::Reinhard) => TonemapOperator::Reinhard,
+                        Some(TonemapOperatorCfg::Aces) => TonemapOperator::Aces,
+                        Some(TonemapOperatorCfg::AgX) => TonemapOperator::AgX,
+                        None => defaults.operator,
+                    },
+                    exposure: cfg.exposure.unwrap_or(defaults.exposure),
+                    auto_exposure: cfg.auto_exposure.unwrap_or(defaults.auto_exposure),
+                };
+                PostProcess::set_tonemapping(&mut self.world, tonemapping);
+            }
+            if let Some(cfg) = &env.outline {
+                let defaults = Outline::default();
+                let outline = Outline {
+                    enabled: cfg.enabled.unwrap_or(defaults.enabled),
+                    width: cfg.width.unwrap_or(defaults.width),
+                    hover_color: cfg.hover_color.map_or(defaults.hover_color, Vec4::from),
+                    grab_color: cfg.grab_color.map_or(defaults.grab_color, Vec4::from),
+                };
+                PostProcess::set_outline(&mut self.world, outline);
+            }
+        }
+
         for node in cfg.nodes.values() {
             let pos = node
                 .pos
                 .map(|pos| Vec3::from_row_slice(&pos))
                 .unwrap_or(Vec3::zeros());
-            let scale = node
-                .scale
-                .map(|scale| Vec3::from_row_slice(&scale))
-                .unwrap_or(Vec3::from_element(1.0));
-            let e = self.world.spawn((Transform::new(pos, scale),));
-            if let Some(ro) = node.render_order {
-                self.world.insert(e, (RenderOrder(ro),)).unwrap();
+            match &node.grid {
+                Some(grid) => {
+                    let [nx, ny, nz] = grid.count;
+                    for x in 0..nx {
+                        for y in 0..ny {
+                            for z in 0..nz {
+                                let offset = Vec3::new(x as f32, y as f32, z as f32) * grid.spacing;
+                                self.insert_node(cfg, node, pos + offset, state);
+                            }
+                        }
+                    }
+                }
+                None => self.insert_node(cfg, node, pos, state),
             }
-            if let Some(rt) = node.render_tags {
-                self.world.insert(e, (RenderTags(rt),)).unwrap();
+        }
+    }
+
+    fn insert_node(&mut self, cfg: &SceneCfg, node: &NodeCfg, pos: Vec3, state: &AppState) {
+        let scale = node
+            .scale
+            .map(|scale| Vec3::from_row_slice(&scale))
+            .unwrap_or(Vec3::from_element(1.0));
+        let mut tr = Transform::new(pos, scale);
+        let light = node.light.as_ref().map(|light| {
+            let (light, direction) = light_from_cfg(light);
+            if let Some(dir) = direction {
+                tr.look_at(pos + dir);
             }
+            light
+        });
+        let e = self.world.spawn((tr,));
+        if let Some(light) = light {
+            self.world.insert(e, (light,)).unwrap();
+        }
+        if let Some(ro) = node.render_order {
+            self.world.insert(e, (RenderOrder(ro),)).unwrap();
+        }
+        if let Some(rt) = node.render_tags {
+            self.world.insert(e, (RenderTags(rt),)).unwrap();
+        }
+        if node.casts_shadows.is_some() || node.receives_shadows.is_some() {
+            let shadows = Shadows {
+                cast: node.casts_shadows.unwrap_or(true),
+                receive: node.receives_shadows.unwrap_or(true),
+            };
+            self.world.insert(e, (shadows,)).unwrap();
+        }
 
-            if let Some(body) = &node.body {
-                let body = RigidBody::cuboid(
-                    components::RigidBodyParams {
-                        pos,
-                        rotation: Vec3::zeros(),
-                        scale,
-                        movable: body.movable.unwrap_or(true),
-                    },
-                    &mut self.physics,
-                );
-                self.world.insert(e, (body,)).unwrap();
+        if let Some(body) = &node.body {
+            let body = RigidBody::cuboid(
+                components::RigidBodyParams {
+                    pos,
+                    rotation: Vec3::zeros(),
+                    scale,
+                    movable: body.movable.unwrap_or(true),
+                },
+                &mut self.physics,
+            );
+            self.world.insert(e, (body,)).unwrap();
+        }
+
+        let mesh = node.mesh.as_ref().map(|mesh| {
+            if let Some(path) = &mesh.path {
+                self.assets.add_mesh_from_file(&state.renderer, path)
+            } else if let Some(prefab) = &mesh.prefab {
+                match prefab {
+                    MeshPrefabCfg::Quad => self
+                        .assets
+                        .add_mesh(render::Mesh::new_quad(&state.renderer), "quad"),
+                    MeshPrefabCfg::Basis => self
+                        .assets
+                        .add_mesh(render::Mesh::new_basis(&state.renderer), "basis"),
+                }
+            } else {
+                panic!("Unable to create mesh");
             }
+        });
+        if let Some(mesh) = mesh {
+            self.world.insert(e, (Mesh(mesh),)).unwrap();
+        }
 
-            if let Some(mesh) = &node.mesh {
-                let mesh = if let Some(path) = &mesh.path {
-                    self.assets.add_mesh_from_file(&state.renderer, path)
-                } else if let Some(prefab) = &mesh.prefab {
-                    match prefab {
-                        MeshPrefabCfg::Quad => self
-                            .assets
-                            .add_mesh(render::Mesh::new_quad(&state.renderer), "quad"),
-                        MeshPrefabCfg::Basis => self
-                            .assets
-                            .add_mesh(render::Mesh::new_basis(&state.renderer), "basis"),
-                    }
-                } else {
-                    panic!("Unable to create mesh");
-                };
-                self.world.insert(e, (Mesh(mesh),)).unwrap();
+        let mats = self.node_materials(cfg, node, mesh, &state.renderer);
+        if !mats.is_empty() {
+            self.world
+                .insert(
+                    e,
+                    (Materials([
+                        mats.first().copied(),
+                        mats.get(1).copied(),
+                        mats.get(2).copied(),
+                        mats.get(3).copied(),
+                    ]),),
+                )
+                .unwrap();
+        } else if node.materials.is_some() {
+            panic!("Unable to create material");
+        }
+        // Skyboxes are full-screen quads placed by the shader, not where their transform says
+        let skybox = mats
+            .iter()
+            .any(|&m| matches!(self.assets.material(m), materials::Material::Skybox(_)));
+        if node.frustum_culled == Some(false) || skybox {
+            self.world.insert(e, (NoCulling,)).unwrap();
+        }
+
+        if let Some(path) = &node.import {
+            gltf_import::import_scene(
+                path,
+                &tr.matrix(),
+                &mut self.world,
+                &state.renderer,
+                &mut self.assets,
+            );
+        }
+
+        for cmp in node.components.as_ref().unwrap_or(&Vec::new()) {
+            match cmp {
+                ComponentCfg::PlayerFocusMarker => {
+                    self.world.insert(e, (PlayerFocusMarker,)).unwrap();
+                }
             }
+        }
+    }
 
-            if let Some(mats) = &node.materials {
-                // TODO Cache, don't re-create. Currently when several nodes use the same material,
-                // only one of them is rendered, must be smth with how the materials work.
-                let mats = mats
-                    .iter()
-                    .filter_map(|mat_name| {
-                        cfg.materials.iter().find_map(|m| match m {
-                            MaterialCfg::Color {
-                                name,
-                                color: [r, g, b],
-                                wireframe,
-                            } if name == mat_name => {
-                                let mat = materials::Material::color(
-                                    &state.renderer,
-                                    &mut self.assets,
-                                    Vec3::new(*r, *g, *b),
-                                    wireframe.unwrap_or(false),
-                                );
-                                Some(self.assets.add_material(mat))
-                            }
+    // Materials listed in the node override the ones coming with the mesh file (e.g. from .mtl)
+    // for parts whose source material has the same name. If no names match, the listed
+    // materials are assigned in part order.
+    fn node_materials(
+        &mut self,
+        cfg: &SceneCfg,
+        node: &NodeCfg,
+        mesh: Option<MeshHandle>,
+        rr: &Renderer,
+    ) -> Vec<MaterialHandle> {
+        let listed = node.materials.as_deref().unwrap_or_default();
+        let part_mats = mesh
+            .map(|mesh| {
+                let mesh = self.assets.mesh(mesh);
+                (0..mesh.parts_count())
+                    .map(|part| mesh.part_material(part).cloned())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
 
-                            MaterialCfg::Textured { name, texture } if name == mat_name => {
-                                let mat = materials::Material::textured(
-                                    &state.renderer,
-                                    &mut self.assets,
-                                    texture,
-                                );
-                                Some(self.assets.add_material(mat))
-                            }
+        let has_part_mats = part_mats.iter().any(Option::is_some);
+        let matched_by_name = part_mats.iter().flatten().any(|m| listed.contains(&m.name));
 
-                            MaterialCfg::Skybox { name, texture } if name == mat_name => {
-                                let mat = materials::Material::skybox(
-                                    &state.renderer,
-                                    &mut self.assets,
-                                    texture,
-                                );
-                                Some(self.assets.add_material(mat))
-                            }
+        if !has_part_mats || (!listed.is_empty() && !matched_by_name) {
+            return listed
+                .iter()
+                .filter_map(|name| self.shared_cfg_material(cfg, name, rr))
+                .take(4) // Max supported materials at the moment.
+                .collect();
+        }
 
-                            _ => None,
-                        })
+        part_mats
+            .iter()
+            .take(4) // Max supported materials at the moment.
+            .map(|part_mat| {
+                part_mat
+                    .as_ref()
+                    .filter(|m| listed.contains(&m.name))
+                    .and_then(|m| self.shared_cfg_material(cfg, &m.name, rr))
+                    .unwrap_or_else(|| {
+                        let mat = match part_mat {
+                            Some(m) => {
+                                materials::Material::from_mesh_material(rr, &mut self.assets, m)
+                            }
+                            None => materials::Material::color(
+                                rr,
+                                &mut self.assets,
+                                Vec4::from_element(1.0),
+                                false,
+                                render::BlendMode::Opaque,
+                            ),
+                        };
+                        self.assets.add_material(mat)
                     })
-                    .take(4) // Max supported materials at the moment.
-                    .collect::<Vec<_>>();
+            })
+            .collect()
+    }
 
-                if !mats.is_empty() {
-                    self.world
-                        .insert(
-                            e,
-                            (Materials([
-                                mats.first().copied(),
-                                mats.get(1).copied(),
-                                mats.get(2).copied(),
-                                mats.get(3).copied(),
-                            ]),),
-                        )
-                        .unwrap();
-                } else {
-                    panic!("Unable to create material");
-                }
+    // Config materials are shared by all the nodes using them, so that they can be batched.
+    fn shared_cfg_material(
+        &mut self,
+        cfg: &SceneCfg,
+        mat_name: &str,
+        rr: &Renderer,
+    ) -> Option<MaterialHandle> {
+        let key = format!("cfg#{mat_name}");
+        if let Some(handle) = self.assets.find_material(&key) {
+            return Some(handle);
+        }
+        let mat = self.cfg_material(cfg, mat_name, rr)?;
+        Some(self.assets.add_material_with(&key, |_| mat))
+    }
+
+    fn cfg_material(
+        &mut self,
+        cfg: &SceneCfg,
+        mat_name: &str,
+        rr: &Renderer,
+    ) -> Option<materials::Material> {
+        cfg.materials.iter().find_map(|m| match m {
+            MaterialCfg::Color {
+                name,
+                color: [r, g, b],
+                alpha,
+                wireframe,
+                blend,
+            } if name == mat_name => Some(materials::Material::color(
+                rr,
+                &mut self.assets,
+                Vec4::new(*r, *g, *b, alpha.unwrap_or(1.0)),
+                wireframe.unwrap_or(false),
+                blend_mode_from_cfg(blend),
+            )),
+
+            MaterialCfg::Textured {
+                name,
+                texture,
+                filter,
+                address_mode,
+                uv_scale,
+                uv_offset,
+                blend,
+            } if name == mat_name => {
+                let defaults = materials::TexturedOptions::default();
+                let options = materials::TexturedOptions {
+                    sampler: render::SamplerOptions {
+                        filter: match filter {
+                            Some(TextureFilterCfg::Nearest) => wgpu::FilterMode::Nearest,
+                            Some(TextureFilterCfg::Linear) => wgpu::FilterMode::Linear,
+                            None => defaults.sampler.filter,
+                        },
+                        address_mode: match address_mode {
+                            Some(TextureAddressModeCfg::Repeat) => wgpu::AddressMode::Repeat,
+                            Some(TextureAddressModeCfg::Mirror) => wgpu::AddressMode::MirrorRepeat,
+                            Some(TextureAddressModeCfg::Clamp) => wgpu::AddressMode::ClampToEdge,
+                            None => defaults.sampler.address_mode,
+                        },
+                        ..defaults.sampler
+                    },
+                    uv_scale: uv_scale.map_or(defaults.uv_scale, Vec2::from),
+                    uv_offset: uv_offset.map_or(defaults.uv_offset, Vec2::from),
+                    blend_mode: blend_mode_from_cfg(blend),
+                };
+                Some(materials::Material::textured(
+                    rr,
+                    &mut self.assets,
+                    texture,
+                    &options,
+                ))
             }
 
-            for cmp in node.components.as_ref().unwrap_or(&Vec::new()) {
-                match cmp {
-                    ComponentCfg::PlayerFocusMarker => {
-                        self.world.insert(e, (PlayerFocusMarker,)).unwrap();
-                    }
+            MaterialCfg::Skybox {
+                name,
+                texture,
+                faces,
+            } if name == mat_name => Some(match (texture, faces) {
+                (Some(texture), _) => materials::Material::skybox(rr, &mut self.assets, texture),
+                (None, Some(faces)) => {
+                    let tex = self.assets.add_cube_texture_from_faces(rr, faces);
+                    materials::Material::skybox_from_handle(rr, &mut self.assets, tex)
                 }
+                (None, None) => panic!("Skybox material {name} has neither texture nor faces"),
+            }),
+
+            MaterialCfg::Pbr {
+                name,
+                base_color,
+                base_color_texture,
+                metallic,
+                roughness,
+                metallic_roughness_texture,
+                normal_texture,
+                occlusion_texture,
+                emissive,
+                emissive_texture,
+                blend,
+            } if name == mat_name => {
+                let mut texture = |path: &Option<String>, srgb: bool| {
+                    path.as_ref()
+                        .map(|p| self.assets.add_2d_texture_from_file(rr, p, srgb))
+                };
+                let defaults = materials::PbrOptions::default();
+                let options = materials::PbrOptions {
+                    base_color: base_color.map_or(defaults.base_color, Vec4::from),
+                    base_color_texture: texture(base_color_texture, true),
+                    metallic: metallic.unwrap_or(defaults.metallic),
+                    roughness: roughness.unwrap_or(defaults.roughness),
+                    metallic_roughness_texture: texture(metallic_roughness_texture, false),
+                    normal_texture: texture(normal_texture, false),
+                    occlusion_texture: texture(occlusion_texture, false),
+                    emissive: emissive.map_or(defaults.emissive, Vec3::from),
+                    emissive_texture: texture(emissive_texture, true),
+                    blend_mode: blend_mode_from_cfg(blend),
+                    ..defaults
+                };
+                Some(materials::Material::pbr(rr, &mut self.assets, &options))
             }
-        }
+
+            _ => None,
+        })
     }
 
     fn sync_physics(&mut self) {
         for (_, (t, body)) in self.world.query_mut::<(&mut Transform, &RigidBody)>() {
             let body = self.physics.body(body.handle());
             t.set(*body.translation(), *body.rotation().inverse().quaternion());
+
+            // Arrows cover the distance of the next tenth of a second
+            if body.is_dynamic() && !body.is_sleeping() {
+                let pos = *body.translation();
+                self.debug_draw.arrow(
+                    pos,
+                    pos + body.linvel() * 0.1,
+                    DebugStyle::new(Vec4::new(1.0, 0.5, 0.0, 1.0)).on_top(),
+                );
+            }
         }
     }
 }
+
+// Returns the light along with the direction it should point at, if any.
+fn light_from_cfg(cfg: &LightCfg) -> (Light, Option<Vec3>) {
+    match *cfg {
+        LightCfg::Directional {
+            color,
+            intensity,
+            direction,
+        } => (
+            Light {
+                kind: LightKind::Directional,
+                color: Vec3::from(color),
+                intensity: intensity.unwrap_or(1.0),
+            },
+            Some(Vec3::from(direction)),
+        ),
+        LightCfg::Point {
+            color,
+            intensity,
+            range,
+        } => (
+            Light {
+                kind: LightKind::Point { range },
+                color: Vec3::from(color),
+                intensity: intensity.unwrap_or(1.0),
+            },
+            None,
+        ),
+        LightCfg::Spot {
+            color,
+            intensity,
+            direction,
+            range,
+            inner_angle,
+            outer_angle,
+        } => (
+            Light {
+                kind: LightKind::Spot {
+                    range,
+                    inner_angle: inner_angle.to_radians(),
+                    outer_angle: outer_angle.to_radians(),
+                },
+                color: Vec3::from(color),
+                intensity: intensity.unwrap_or(1.0),
+            },
+            Some(Vec3::from(direction)),
+        ),
+    }
+}
+
+fn blend_mode_from_cfg(cfg: &Option<BlendModeCfg>) -> render::BlendMode {
+    match cfg {
+        Some(BlendModeCfg::Opaque) | None => render::BlendMode::Opaque,
+        Some(BlendModeCfg::AlphaBlend) => render::BlendMode::AlphaBlend,
+        Some(BlendModeCfg::Additive) => render::BlendMode::Additive,
+        Some(BlendModeCfg::Cutout(cutoff)) => render::BlendMode::Cutout(*cutoff),
+    }
+}
+
+fn post_effect_from_cfg(cfg: &PostEffectCfg) -> PostEffect {
+    let (kind, enabled) = match cfg {
+        PostEffectCfg::Vignette {
+            enabled,
+            radius,
+            softness,
+            strength,
+        } => (
+            PostEffectKind::Vignette {
+                radius: radius.unwrap_or(1.0),
+                softness: softness.unwrap_or(1.0),
+                strength: strength.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+        PostEffectCfg::Gamma { enabled, gamma } => (
+            PostEffectKind::Gamma {
+                gamma: gamma.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+        PostEffectCfg::ColorGrading {
+            enabled,
+            lut,
+            strength,
+        } => (
+            PostEffectKind::ColorGrading {
+                lut: lut.clone(),
+                strength: strength.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+        PostEffectCfg::Fxaa { enabled } => (PostEffectKind::Fxaa, enabled),
+        PostEffectCfg::ChromaticAberration { enabled, strength } => (
+            PostEffectKind::ChromaticAberration {
+                strength: strength.unwrap_or(0.005),
+            },
+            enabled,
+        ),
+        PostEffectCfg::Bloom {
+            enabled,
+            threshold,
+            intensity,
+            radius,
+        } => (
+            PostEffectKind::Bloom {
+                threshold: threshold.unwrap_or(1.0),
+                intensity: intensity.unwrap_or(0.5),
+                radius: radius.unwrap_or(1.0),
+            },
+            enabled,
+        ),
+    };
+    PostEffect {
+        kind,
+        enabled: enabled.unwrap_or(true),
+    }
+}
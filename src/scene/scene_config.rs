@@ -17,21 +17,162 @@ pub enum ComponentCfg {
     PlayerFocusMarker,
 }
 
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum TextureFilterCfg {
+    Nearest,
+    Linear,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum TextureAddressModeCfg {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+// Transparent modes are drawn after the opaque ones, sorted back-to-front.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum BlendModeCfg {
+    Opaque,
+    AlphaBlend,
+    Additive,
+    // Alpha cutoff
+    Cutout(f32),
+}
+
 #[derive(Deserialize, Debug)]
 pub enum MaterialCfg {
     Color {
         name: String,
         color: [f32; 3],
+        alpha: Option<f32>,
         wireframe: Option<bool>,
+        blend: Option<BlendModeCfg>,
     },
     Textured {
         name: String,
         texture: String,
+        filter: Option<TextureFilterCfg>,
+        address_mode: Option<TextureAddressModeCfg>,
+        uv_scale: Option<[f32; 2]>,
+        uv_offset: Option<[f32; 2]>,
+        blend: Option<BlendModeCfg>,
     },
+    // Either a DDS cubemap `texture` or six `faces` images in the px, nx, py, ny, pz, nz order.
     Skybox {
         name: String,
-        texture: String,
+        texture: Option<String>,
+        faces: Option<[String; 6]>,
+    },
+    // Metallic-roughness model as in glTF: textures are multiplied by the factors. Roughness
+    // is read from the G channel and metallic from B.
+    Pbr {
+        name: String,
+        base_color: Option<[f32; 4]>,
+        base_color_texture: Option<String>,
+        metallic: Option<f32>,
+        roughness: Option<f32>,
+        metallic_roughness_texture: Option<String>,
+        normal_texture: Option<String>,
+        occlusion_texture: Option<String>,
+        emissive: Option<[f32; 3]>,
+        emissive_texture: Option<String>,
+        blend: Option<BlendModeCfg>,
+    },
+}
+
+// Colors are linear, angles are in degrees.
+#[derive(Deserialize, Debug)]
+pub enum LightCfg {
+    Directional {
+        color: [f32; 3],
+        intensity: Option<f32>,
+        direction: [f32; 3],
+    },
+    Point {
+        color: [f32; 3],
+        intensity: Option<f32>,
+        range: f32,
+    },
+    Spot {
+        color: [f32; 3],
+        intensity: Option<f32>,
+        direction: [f32; 3],
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+// Effects are enabled unless `enabled: false`.
+#[derive(Deserialize, Debug)]
+pub enum PostEffectCfg {
+    Vignette {
+        enabled: Option<bool>,
+        radius: Option<f32>,
+        softness: Option<f32>,
+        strength: Option<f32>,
     },
+    Gamma {
+        enabled: Option<bool>,
+        gamma: Option<f32>,
+    },
+    ColorGrading {
+        enabled: Option<bool>,
+        lut: String,
+        strength: Option<f32>,
+    },
+    Fxaa {
+        enabled: Option<bool>,
+    },
+    ChromaticAberration {
+        enabled: Option<bool>,
+        strength: Option<f32>,
+    },
+    Bloom {
+        enabled: Option<bool>,
+        threshold: Option<f32>,
+        intensity: Option<f32>,
+        radius: Option<f32>,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum TonemapOperatorCfg {
+    None,
+    Reinhard,
+    Aces,
+    AgX,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TonemappingCfg {
+    // ACES by default
+    pub operator: Option<TonemapOperatorCfg>,
+    // Compensation in EV
+    pub exposure: Option<f32>,
+    pub auto_exposure: Option<bool>,
+}
+
+// Around the hovered and the grabbed entity, enabled by default. Colors are RGBA.
+#[derive(Deserialize, Debug)]
+pub struct OutlineCfg {
+    pub enabled: Option<bool>,
+    // In pixels
+    pub width: Option<f32>,
+    pub hover_color: Option<[f32; 4]>,
+    pub grab_color: Option<[f32; 4]>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EnvironmentCfg {
+    pub ambient: [f32; 3],
+    // Cubemap for the image based lighting of PBR materials, which use `ambient` without it.
+    pub map: Option<String>,
+    // Applied in order to the HDR image, before tonemapping. Just a vignette when not specified.
+    pub post_process: Option<Vec<PostEffectCfg>>,
+    pub tonemapping: Option<TonemappingCfg>,
+    pub outline: Option<OutlineCfg>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -57,10 +198,34 @@ pub struct NodeCfg {
     pub mesh: Option<MeshCfg>,
     pub materials: Option<Vec<String>>,
     pub components: Option<Vec<ComponentCfg>>,
+    pub light: Option<LightCfg>,
+    pub casts_shadows: Option<bool>,
+    pub receives_shadows: Option<bool>,
+    // For meshes that aren't where their transform says, skyboxes opt out on their own.
+    pub frustum_culled: Option<bool>,
+    // Path to a glTF file whose scene is spawned relative to this node.
+    pub import: Option<String>,
+    // Spawns copies of the node in a grid starting at its position.
+    pub grid: Option<GridCfg>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GridCfg {
+    pub count: [u32; 3],
+    pub spacing: f32,
+}
+
+// Read before the renderer is created.
+#[derive(Deserialize, Debug)]
+pub struct RenderCfg {
+    // MSAA sample count: 1, 2, 4 or 8, lowered to what the adapter supports.
+    pub msaa: Option<u32>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct SceneCfg {
+    pub render: Option<RenderCfg>,
+    pub environment: Option<EnvironmentCfg>,
     pub materials: Vec<MaterialCfg>,
     pub nodes: HashMap<String, NodeCfg>,
 }
@@ -5,7 +5,8 @@ use crate::input::Input;
 use crate::render::{Renderer, SurfaceSize};
 
 pub struct AppState<'a> {
-    pub window: Arc<Window>,
+    // None when rendering headless
+    pub window: Option<Arc<Window>>,
     pub renderer: Renderer<'a>,
     pub input: Input,
     // TODO This should probably go into smth like "frame state"